@@ -7,6 +7,7 @@
 }:
 let
   cfg = config.services.automatic-redshift;
+  settingsFormat = pkgs.formats.toml { };
 in
 {
   options = {
@@ -16,12 +17,21 @@ in
         default = false;
       };
       package = lib.mkPackageOption perSystem.self "automatic-redshift" { };
+      settings = lib.mkOption {
+        type = settingsFormat.type;
+        default = { };
+        description = "Contents of config.toml, see the README for the available settings.";
+      };
     };
   };
 
   config = lib.mkIf cfg.enable {
+    # The daemon authorizes itself as the GeoClue agent of its user
+    services.automatic-redshift.settings.geoclue.agent = lib.mkDefault { };
+
     services.geoclue2 = {
       enable = true;
+      whitelistedAgents = lib.mkOptionDefault [ "automatic-redshift" ];
       appConfig.automatic-redshift = {
         isAllowed = true;
         isSystem = true;
@@ -30,29 +40,15 @@ in
     };
 
     systemd.services = {
-
       automatic-redshift = {
         description = "Automatically adjust screen color temperature based on location and time of day";
-        requires = [ "automatic-redshift-geoclue-agent.service" ];
-        after = [ "automatic-redshift-geoclue-agent.service" ];
-        serviceConfig = {
-          Type = "exec";
-          User = "automatic-redshift";
-          ExecStart = "${cfg.package}/bin/automatic-redshift";
-        };
-        wantedBy = [ "default.target" ];
-      };
-
-      automatic-redshift-geoclue-agent = {
-        description = "Geoclue agent for automatic-redshift";
-        requires = [ "geoclue.service" ];
-        after = [ "geoclue.service" ];
         serviceConfig = {
           Type = "exec";
           User = "automatic-redshift";
-          ExecStart = "${pkgs.geoclue2-with-demo-agent}/libexec/geoclue-2.0/demos/agent";
-          Restart = "on-failure";
-          PrivateTmp = true;
+          ExecStart = "${cfg.package}/bin/automatic-redshift --config ${settingsFormat.generate "config.toml" cfg.settings}";
+          RuntimeDirectory = "automatic-redshift";
+          RuntimeDirectoryMode = "0755";
+          CacheDirectory = "automatic-redshift";
         };
         wantedBy = [ "default.target" ];
       };
@@ -0,0 +1,88 @@
+use jiff::{SignedDuration, Timestamp};
+#[cfg(test)]
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Source of the current time for the scheduling loop
+pub trait Clock {
+    fn now(&self) -> Timestamp;
+
+    /// Real time to wait for `duration` to pass on this clock
+    fn real_duration(&self, duration: Duration) -> Duration;
+}
+
+/// The wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+
+    fn real_duration(&self, duration: Duration) -> Duration {
+        duration
+    }
+}
+
+/// A clock starting at an arbitrary time and running `speed` times faster than real time
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualClock {
+    start: Timestamp,
+    started: Instant,
+    speed: f64,
+}
+
+impl VirtualClock {
+    pub fn new(start: Timestamp, speed: f64) -> Self {
+        Self {
+            start,
+            started: Instant::now(),
+            speed,
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Timestamp {
+        let elapsed =
+            SignedDuration::from_secs_f64(self.started.elapsed().as_secs_f64() * self.speed);
+
+        self.start.saturating_add(elapsed).unwrap_or(Timestamp::MAX)
+    }
+
+    fn real_duration(&self, duration: Duration) -> Duration {
+        duration.div_f64(self.speed)
+    }
+}
+
+/// A clock that only moves when told to
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Cell<Timestamp>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(start: Timestamp) -> Self {
+        Self {
+            now: Cell::new(start),
+        }
+    }
+
+    pub fn advance(&self, duration: SignedDuration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.now.get()
+    }
+
+    fn real_duration(&self, duration: Duration) -> Duration {
+        duration
+    }
+}
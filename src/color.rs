@@ -1,17 +1,47 @@
 use color_eyre::eyre::bail;
+use std::fmt::{self, Display};
 use vek::Rgb;
 
-/// Fill a color ramp based on a temperature
+/// Temperature and brightness applied to the outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSetting {
+    /// Color temperature in Kelvin
+    pub temperature: u16,
+    /// Brightness in the range `0.0..=1.0`
+    pub brightness: f64,
+}
+
+impl ColorSetting {
+    pub const fn new(temperature: u16) -> Self {
+        Self {
+            temperature,
+            brightness: 1.0,
+        }
+    }
+}
+
+impl Display for ColorSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} K", self.temperature)?;
+        let brightness = (self.brightness * 100.0).round();
+        if brightness < 100.0 {
+            write!(f, ", {brightness}% brightness")?;
+        }
+        Ok(())
+    }
+}
+
+/// Fill a color ramp based on a temperature and brightness
 pub fn fill_colorramp(
     r: &mut [u16],
     g: &mut [u16],
     b: &mut [u16],
     ramp_size: usize,
-    temperature: u16,
+    color: ColorSetting,
 ) -> color_eyre::Result<()> {
-    let temperature_color = find_interpolated_temperature_color(temperature as f64)?;
+    let temperature_color = find_interpolated_temperature_color(color.temperature as f64)?;
 
-    let max_intensity = u16::MAX as f64;
+    let max_intensity = u16::MAX as f64 * color.brightness.clamp(0.0, 1.0);
     let step = max_intensity / (ramp_size - 1) as f64;
 
     for i in 0..ramp_size {
@@ -26,7 +56,7 @@ pub fn fill_colorramp(
 }
 
 fn find_interpolated_temperature_color(temperature: f64) -> color_eyre::Result<Rgb<f64>> {
-    if temperature < 1000.0 || temperature > 10000.0 {
+    if !(1000.0..=10000.0).contains(&temperature) {
         bail!("Temperature must be between 1000 and 10000");
     }
 
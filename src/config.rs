@@ -0,0 +1,1251 @@
+use crate::{
+    color::ColorSetting,
+    geoclue::AccuracyLevel,
+    location::{Coordinates, Source},
+    places::ActiveNetwork,
+    schedule::SunEvent,
+    transition::Transition,
+    wayland::OutputInfo,
+};
+use color_eyre::eyre::{WrapErr, bail, eyre};
+use jiff::{
+    SignedDuration,
+    civil::{Date, Time, Weekday},
+};
+use serde::{Deserialize, Deserializer, de};
+use std::{
+    env,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Daemon configuration, read from `$XDG_CONFIG_HOME/automatic-redshift/config.toml`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Temperature between sunrise and sunset, in Kelvin
+    pub day_temperature: u16,
+    /// Temperature between dusk and dawn, in Kelvin
+    pub night_temperature: u16,
+    /// Shape of the ramps between keyframes
+    pub transition: Transition,
+    /// Custom schedule, replaces the day and night temperatures when not empty
+    pub keyframes: Vec<Keyframe>,
+    /// Bedtime schedule derived from a wake time
+    pub bedtime: Option<Bedtime>,
+    /// Day and night temperatures varying with the season
+    pub season: Option<Season>,
+    /// Follow the altitude of the sun instead of the keyframe schedule
+    pub altitude_curve: Option<AltitudeCurve>,
+    /// Settings replacing the ones above on matching days, the first match wins
+    pub overrides: Vec<Override>,
+    /// Which location providers are used and when to fall back to the next one
+    pub location: Location,
+    /// How precisely the location is kept and whether it is logged
+    pub privacy: Privacy,
+    /// How precise and how frequent the locations requested from GeoClue are
+    pub geoclue: GeoClue,
+    /// Take the location from a GPS receiver through gpsd
+    pub gpsd: Option<Gpsd>,
+    /// Look up the location from the public IP address
+    pub ip_geolocation: Option<IpGeolocation>,
+    /// Fixed locations selected by the active NetworkManager connection
+    pub places: Vec<Place>,
+    /// Settings of single outputs, the first matching entry applies
+    pub outputs: Vec<OutputConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            day_temperature: 6500,
+            night_temperature: 4000,
+            transition: Transition::default(),
+            keyframes: Vec::new(),
+            bedtime: None,
+            season: None,
+            altitude_curve: None,
+            overrides: Vec::new(),
+            location: Location::default(),
+            privacy: Privacy::default(),
+            geoclue: GeoClue::default(),
+            gpsd: None,
+            ip_geolocation: None,
+            places: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file, falling back to the defaults if it doesn't exist
+    ///
+    /// Without an explicit `path` the default location is used.
+    pub fn load(path: Option<&Path>) -> color_eyre::Result<Self> {
+        let Some(path) = path.map(Path::to_path_buf).or_else(config_path) else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).wrap_err_with(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let mut config: Self = toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse {}", path.display()))?;
+
+        let config_dir = path.parent().unwrap_or(Path::new("."));
+        for day_override in &mut config.overrides {
+            day_override.load_dates_file(config_dir)?;
+        }
+
+        config.validate()?;
+        for day_override in &config.overrides {
+            config
+                .with_override(day_override)
+                .validate()
+                .wrap_err_with(|| format!("Invalid override {:?}", day_override.name))?;
+        }
+
+        Ok(config)
+    }
+
+    /// The override that applies on `date`, if any
+    pub fn active_override(&self, date: Date) -> Option<&Override> {
+        self.overrides
+            .iter()
+            .find(|day_override| day_override.matches(date))
+    }
+
+    /// Copy of this configuration with the settings of `day_override` applied
+    pub fn with_override(&self, day_override: &Override) -> Config {
+        let Override {
+            day_temperature,
+            night_temperature,
+            transition,
+            keyframes,
+            bedtime,
+            season,
+            altitude_curve,
+            ..
+        } = day_override.clone();
+
+        Config {
+            day_temperature: day_temperature.unwrap_or(self.day_temperature),
+            night_temperature: night_temperature.unwrap_or(self.night_temperature),
+            transition: transition.unwrap_or(self.transition),
+            keyframes: keyframes.unwrap_or_else(|| self.keyframes.clone()),
+            bedtime: bedtime.or_else(|| self.bedtime.clone()),
+            season: season.or_else(|| self.season.clone()),
+            altitude_curve: altitude_curve.or_else(|| self.altitude_curve.clone()),
+            overrides: Vec::new(),
+            location: self.location.clone(),
+            privacy: self.privacy.clone(),
+            geoclue: self.geoclue.clone(),
+            gpsd: self.gpsd.clone(),
+            ip_geolocation: self.ip_geolocation.clone(),
+            places: self.places.clone(),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    /// Copy of this configuration with the day and night temperatures of the season applied
+    ///
+    /// `month` is in the range `1..=12`, `day_length` is the time from sunrise to sunset.
+    pub fn with_season(&self, month: i8, day_length: SignedDuration) -> Config {
+        let Some(season) = &self.season else {
+            return self.clone();
+        };
+        let month_index = (month - 1) as usize;
+
+        let day_temperature = season
+            .day_temperature_by_month
+            .map_or(self.day_temperature, |temperatures| {
+                temperatures[month_index]
+            });
+        let mut night_temperature = season
+            .night_temperature_by_month
+            .map_or(self.night_temperature, |temperatures| {
+                temperatures[month_index]
+            });
+
+        if let Some(scaling) = &season.day_length_scaling {
+            night_temperature = scaling.night_temperature(day_length, night_temperature);
+        }
+
+        Config {
+            day_temperature,
+            night_temperature,
+            ..self.clone()
+        }
+    }
+
+    /// Keyframes of the base schedule, before bedtime is applied
+    pub fn schedule_keyframes(&self) -> Vec<Keyframe> {
+        if !self.keyframes.is_empty() {
+            return self.keyframes.clone();
+        }
+
+        let day = ColorSetting::new(self.day_temperature);
+        let night = ColorSetting::new(self.night_temperature);
+
+        vec![
+            Keyframe::new(Anchor::Sun(SunEvent::Dawn), SignedDuration::ZERO, night),
+            Keyframe::new(Anchor::Sun(SunEvent::Sunrise), SignedDuration::ZERO, day),
+            Keyframe::new(Anchor::Sun(SunEvent::Sunset), SignedDuration::ZERO, day),
+            Keyframe::new(Anchor::Sun(SunEvent::Dusk), SignedDuration::ZERO, night),
+        ]
+    }
+
+    /// Location providers in order of priority
+    ///
+    /// Unless configured, the location comes from gpsd, IP geolocation and places if they are
+    /// set up, otherwise from GeoClue, falling back to the cached location, the time zone and
+    /// the static coordinates.
+    pub fn location_providers(&self) -> Vec<Source> {
+        if !self.location.providers.is_empty() {
+            return self.location.providers.clone();
+        }
+
+        let mut providers = Vec::new();
+        if self.gpsd.is_some() {
+            providers.push(Source::Gpsd);
+        }
+        if self.ip_geolocation.is_some() {
+            providers.push(Source::IpGeolocation);
+        }
+        if !self.places.is_empty() {
+            providers.push(Source::Places);
+        }
+        if providers.is_empty() {
+            providers.push(Source::GeoClue);
+        }
+        providers.extend([Source::Cached, Source::TimeZone]);
+        if self.location.static_location.is_some() {
+            providers.push(Source::Static);
+        }
+
+        providers
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        let bedtime_temperature = self.bedtime.as_ref().map(|b| b.late_night_temperature);
+        let season_temperatures = self
+            .season
+            .as_ref()
+            .map(Season::temperatures)
+            .unwrap_or_default();
+        let altitude_points = self
+            .altitude_curve
+            .as_ref()
+            .map_or(&[][..], |curve| &curve.points);
+        let temperatures = [self.day_temperature, self.night_temperature]
+            .into_iter()
+            .chain(bedtime_temperature)
+            .chain(season_temperatures)
+            .chain(self.keyframes.iter().map(|keyframe| keyframe.temperature))
+            .chain(altitude_points.iter().map(|point| point.temperature));
+
+        for temperature in temperatures {
+            if !(1000..=10000).contains(&temperature) {
+                bail!("Temperature {temperature} K must be between 1000 and 10000");
+            }
+        }
+
+        for keyframe in &self.keyframes {
+            if !(0.1..=1.0).contains(&keyframe.brightness) {
+                bail!(
+                    "Brightness {} of keyframe at {} must be between 0.1 and 1.0",
+                    keyframe.brightness,
+                    keyframe.anchor
+                );
+            }
+        }
+
+        for point in altitude_points {
+            if !(0.1..=1.0).contains(&point.brightness) {
+                bail!(
+                    "Brightness {} of altitude point at {}° must be between 0.1 and 1.0",
+                    point.brightness,
+                    point.altitude
+                );
+            }
+        }
+
+        if let Some(curve) = &self.altitude_curve {
+            if !self.keyframes.is_empty() {
+                bail!("Keyframes and altitude_curve can't be used together");
+            }
+            curve.validate()?;
+        }
+
+        if let Some(bedtime) = &self.bedtime {
+            bedtime.validate()?;
+        }
+
+        let scaling = self
+            .season
+            .as_ref()
+            .and_then(|s| s.day_length_scaling.as_ref());
+        if let Some(scaling) = scaling
+            && scaling.short_day >= scaling.long_day
+        {
+            bail!("Season short_day must be shorter than long_day");
+        }
+
+        self.geoclue.validate()?;
+
+        self.location.validate()?;
+        self.privacy.validate()?;
+        if self.location.providers.contains(&Source::Places) && self.places.is_empty() {
+            bail!("The places location provider needs places");
+        }
+        if let Some(ip_geolocation) = &self.ip_geolocation {
+            ip_geolocation.validate()?;
+        }
+        for place in &self.places {
+            place.validate()?;
+        }
+        for output in &self.outputs {
+            output.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Priority list of the location providers
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Location {
+    /// Providers to use, the first one delivering locations wins, derived from the other
+    /// location settings if empty
+    pub providers: Vec<Source>,
+    /// How long to wait for a location before starting the next provider
+    pub timeout: SignedDuration,
+    /// Coordinates of the static provider
+    #[serde(rename = "static")]
+    pub static_location: Option<StaticLocation>,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            timeout: SignedDuration::from_secs(30),
+            static_location: None,
+        }
+    }
+}
+
+impl Location {
+    fn validate(&self) -> color_eyre::Result<()> {
+        if !self.timeout.is_positive() {
+            bail!("Location timeout must be positive");
+        }
+        for (index, provider) in self.providers.iter().enumerate() {
+            if self.providers[..index].contains(provider) {
+                bail!("Location provider {provider} is listed more than once");
+            }
+        }
+        if self.providers.contains(&Source::Static) && self.static_location.is_none() {
+            bail!("The static location provider needs coordinates in [location.static]");
+        }
+        if let Some(static_location) = &self.static_location {
+            static_location.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fixed coordinates used as the last resort
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaticLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    #[serde(default)]
+    pub altitude: f64,
+}
+
+impl StaticLocation {
+    pub fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.altitude,
+        }
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            bail!("Static location has invalid coordinates");
+        }
+
+        Ok(())
+    }
+}
+
+/// Without the coordinates, so logging the configuration doesn't reveal the location
+impl fmt::Debug for StaticLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticLocation")
+            .field("altitude", &self.altitude)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Limits on what the daemon keeps and logs about the location
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Privacy {
+    /// Decimal places latitude and longitude are rounded to, 2 is about a kilometer
+    pub coordinate_decimals: u8,
+    /// Include the coordinates in the log
+    pub log_coordinates: bool,
+}
+
+impl Default for Privacy {
+    fn default() -> Self {
+        Self {
+            coordinate_decimals: 2,
+            log_coordinates: false,
+        }
+    }
+}
+
+impl Privacy {
+    fn validate(&self) -> color_eyre::Result<()> {
+        if self.coordinate_decimals > 8 {
+            bail!("Privacy coordinate_decimals must be at most 8");
+        }
+
+        Ok(())
+    }
+}
+
+/// Location updates requested from GeoClue
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeoClue {
+    /// Coarser levels reveal less about the location
+    pub accuracy_level: AccuracyLevel,
+    /// Minimum distance in meters to move before a new location is reported, 0 for any change
+    pub distance_threshold: u32,
+    /// Minimum time between location updates, 0 for no limit
+    pub time_threshold: SignedDuration,
+    /// Act as the GeoClue agent of the user, so no desktop environment agent is needed
+    pub agent: Option<GeoClueAgent>,
+}
+
+impl Default for GeoClue {
+    fn default() -> Self {
+        Self {
+            accuracy_level: AccuracyLevel::City,
+            distance_threshold: 10000,
+            time_threshold: SignedDuration::ZERO,
+            agent: None,
+        }
+    }
+}
+
+impl GeoClue {
+    /// Time threshold in whole seconds, as GeoClue expects it
+    pub fn time_threshold_secs(&self) -> u32 {
+        self.time_threshold.as_secs() as u32
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        if self.time_threshold.is_negative() {
+            bail!("GeoClue time_threshold must not be negative");
+        }
+        if self.time_threshold.as_secs() > i64::from(u32::MAX) {
+            bail!(
+                "GeoClue time_threshold must be at most {} seconds",
+                u32::MAX
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Which apps the built-in GeoClue agent lets access the location
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeoClueAgent {
+    /// Desktop IDs of the apps that are authorized
+    pub allowed_apps: Vec<String>,
+    /// Most precise accuracy level granted to any app
+    pub max_accuracy_level: AccuracyLevel,
+}
+
+impl Default for GeoClueAgent {
+    fn default() -> Self {
+        Self {
+            allowed_apps: vec!["automatic-redshift".to_owned()],
+            max_accuracy_level: AccuracyLevel::Exact,
+        }
+    }
+}
+
+/// Connection to gpsd
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gpsd {
+    /// Host and port gpsd listens on
+    pub address: String,
+    /// Minimum distance in meters to move before a new location is reported, 0 for any change
+    pub distance_threshold: u32,
+}
+
+impl Default for Gpsd {
+    fn default() -> Self {
+        Self {
+            address: "localhost:2947".to_owned(),
+            distance_threshold: 1000,
+        }
+    }
+}
+
+/// HTTP endpoint returning the location of the public IP address as JSON
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IpGeolocation {
+    pub url: String,
+    /// Dot-separated path to the latitude in the response, like `location.latitude`
+    pub latitude_field: String,
+    /// Dot-separated path to the longitude in the response
+    pub longitude_field: String,
+    /// How long a looked up location is used before querying the endpoint again
+    pub ttl: SignedDuration,
+}
+
+impl Default for IpGeolocation {
+    fn default() -> Self {
+        Self {
+            url: "http://ip-api.com/json".to_owned(),
+            latitude_field: "lat".to_owned(),
+            longitude_field: "lon".to_owned(),
+            ttl: SignedDuration::from_hours(6),
+        }
+    }
+}
+
+impl IpGeolocation {
+    fn validate(&self) -> color_eyre::Result<()> {
+        if self.ttl <= SignedDuration::ZERO {
+            bail!("IP geolocation ttl must be positive");
+        }
+
+        Ok(())
+    }
+}
+
+/// A location recognized by the network connections active there
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Place {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    #[serde(default)]
+    pub altitude: f64,
+    /// NetworkManager connection IDs active at this place
+    #[serde(default)]
+    pub connections: Vec<String>,
+    /// Wi-Fi network names seen at this place
+    #[serde(default)]
+    pub ssids: Vec<String>,
+}
+
+impl Place {
+    pub fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.altitude,
+        }
+    }
+
+    /// Whether any of the active `networks` belongs to this place
+    pub fn matches(&self, networks: &[ActiveNetwork]) -> bool {
+        networks.iter().any(|network| {
+            self.connections.contains(&network.id)
+                || network
+                    .ssid
+                    .as_ref()
+                    .is_some_and(|ssid| self.ssids.contains(ssid))
+        })
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        if !(-90.0..=90.0).contains(&self.latitude) || !(-180.0..=180.0).contains(&self.longitude) {
+            bail!("Place {:?} has invalid coordinates", self.name);
+        }
+        if self.connections.is_empty() && self.ssids.is_empty() {
+            bail!("Place {:?} needs connections or ssids", self.name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Without the coordinates, like [`StaticLocation`]
+impl fmt::Debug for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Place")
+            .field("name", &self.name)
+            .field("altitude", &self.altitude)
+            .field("connections", &self.connections)
+            .field("ssids", &self.ssids)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Settings of the outputs matching all of the given fields, see `automatic-redshift list-outputs`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Connector name, like `DP-1`
+    pub name: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    /// Part of the description, which usually includes the serial number
+    pub description: Option<String>,
+    /// Leave the gamma of the output to other clients
+    pub enabled: bool,
+    /// Factor the scheduled brightness is multiplied with, in the range `0.1..=1.0`
+    pub brightness: f64,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            make: None,
+            model: None,
+            description: None,
+            enabled: true,
+            brightness: 1.0,
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn matches(&self, info: &OutputInfo) -> bool {
+        let equals = |expected: &Option<String>, actual: &Option<String>| {
+            expected.is_none() || expected == actual
+        };
+        let description_matches = self.description.as_ref().is_none_or(|expected| {
+            info.description
+                .as_ref()
+                .is_some_and(|description| description.contains(expected.as_str()))
+        });
+
+        equals(&self.name, &info.name)
+            && equals(&self.make, &info.make)
+            && equals(&self.model, &info.model)
+            && description_matches
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        if self.name.is_none()
+            && self.make.is_none()
+            && self.model.is_none()
+            && self.description.is_none()
+        {
+            bail!("Outputs need a name, make, model or description to match");
+        }
+        if !(0.1..=1.0).contains(&self.brightness) {
+            bail!(
+                "Output brightness {} must be between 0.1 and 1.0",
+                self.brightness
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Schedule that follows a fixed sleep window instead of, or on top of, the sun
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bedtime {
+    /// Local time the day temperature is reached
+    pub wake_time: Time,
+    /// Bedtime is `wake_time` minus `sleep_duration`
+    pub sleep_duration: SignedDuration,
+    /// How long before bedtime the night temperature is reached
+    pub wind_down: SignedDuration,
+    /// How long before bedtime the late night temperature is reached
+    pub late_night_duration: SignedDuration,
+    /// Temperature from the late night stage until wake time, in Kelvin
+    pub late_night_temperature: u16,
+    /// Duration of the ramps between the stages
+    pub ramp_duration: SignedDuration,
+    /// Ignore the sun entirely, otherwise the warmer of both schedules is used
+    pub replace_solar: bool,
+}
+
+impl Default for Bedtime {
+    fn default() -> Self {
+        Self {
+            wake_time: Time::constant(7, 0, 0, 0),
+            sleep_duration: SignedDuration::from_hours(8),
+            wind_down: SignedDuration::from_hours(3),
+            late_night_duration: SignedDuration::from_hours(1),
+            late_night_temperature: 3400,
+            ramp_duration: SignedDuration::from_mins(30),
+            replace_solar: false,
+        }
+    }
+}
+
+impl Bedtime {
+    /// Keyframes of the bedtime schedule, anchored to the wake time
+    ///
+    /// The stages are:
+    /// - day from wake time until the evening ramp
+    /// - night from `wind_down` before bedtime
+    /// - late night from `late_night_duration` before bedtime until wake time
+    pub fn keyframes(&self, config: &Config) -> Vec<Keyframe> {
+        let anchor = Anchor::Time(self.wake_time);
+        let day = ColorSetting::new(config.day_temperature);
+        let night = ColorSetting::new(config.night_temperature);
+        let late_night = ColorSetting::new(self.late_night_temperature);
+
+        let ramp = self.ramp_duration;
+        let night_offset = -self.sleep_duration - self.wind_down;
+        let late_night_offset = -self.sleep_duration - self.late_night_duration;
+
+        vec![
+            Keyframe::new(anchor, -ramp, late_night),
+            Keyframe::new(anchor, SignedDuration::ZERO, day),
+            Keyframe::new(anchor, night_offset - ramp, day),
+            Keyframe::new(anchor, night_offset, night),
+            Keyframe::new(anchor, late_night_offset - ramp, night),
+            Keyframe::new(anchor, late_night_offset, late_night),
+        ]
+    }
+
+    fn validate(&self) -> color_eyre::Result<()> {
+        let durations = [
+            self.sleep_duration,
+            self.wind_down,
+            self.late_night_duration,
+            self.ramp_duration,
+        ];
+        if durations.iter().any(|duration| duration.is_negative()) {
+            bail!("Bedtime durations must not be negative");
+        }
+
+        // The stages must follow each other within a single day
+        if self.late_night_duration + self.ramp_duration > self.wind_down {
+            bail!("Bedtime late_night_duration plus ramp_duration must not exceed wind_down");
+        }
+        if self.sleep_duration + self.wind_down + 2 * self.ramp_duration
+            > SignedDuration::from_hours(24)
+        {
+            bail!("Bedtime sleep_duration plus wind_down must leave room for the day");
+        }
+
+        Ok(())
+    }
+}
+
+/// Temperature following the altitude of the sun over the whole day
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AltitudeCurve {
+    /// Colors at sun altitudes, interpolated in between and sorted by altitude
+    pub points: Vec<AltitudePoint>,
+}
+
+impl Default for AltitudeCurve {
+    fn default() -> Self {
+        Self {
+            points: vec![
+                AltitudePoint::new(-6.0, ColorSetting::new(4000)),
+                AltitudePoint::new(0.0, ColorSetting::new(4800)),
+                AltitudePoint::new(15.0, ColorSetting::new(5800)),
+                AltitudePoint::new(40.0, ColorSetting::new(6500)),
+            ],
+        }
+    }
+}
+
+impl AltitudeCurve {
+    fn validate(&self) -> color_eyre::Result<()> {
+        if self.points.is_empty() {
+            bail!("Altitude curve must have at least one point");
+        }
+        if !self.points.is_sorted_by(|a, b| a.altitude < b.altitude) {
+            bail!("Altitude curve points must be sorted by ascending altitude");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AltitudePoint {
+    /// Altitude of the sun above the horizon in degrees
+    pub altitude: f64,
+    /// Temperature in Kelvin
+    pub temperature: u16,
+    /// Brightness in the range `0.1..=1.0`
+    #[serde(default = "default_brightness")]
+    pub brightness: f64,
+}
+
+impl AltitudePoint {
+    pub fn new(altitude: f64, color: ColorSetting) -> Self {
+        Self {
+            altitude,
+            temperature: color.temperature,
+            brightness: color.brightness,
+        }
+    }
+
+    pub fn color(&self) -> ColorSetting {
+        ColorSetting {
+            temperature: self.temperature,
+            brightness: self.brightness,
+        }
+    }
+}
+
+/// Temperatures that vary with the month or the length of the day
+///
+/// Only the day and night temperatures are affected, custom keyframes are used as is.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Season {
+    /// Day temperature for every month, January first
+    pub day_temperature_by_month: Option<[u16; 12]>,
+    /// Night temperature for every month, January first
+    pub night_temperature_by_month: Option<[u16; 12]>,
+    /// Milder night temperature on short days
+    pub day_length_scaling: Option<DayLengthScaling>,
+    /// Don't go warmer than a limit before a clock time
+    pub warmth_limit: Option<WarmthLimit>,
+}
+
+impl Season {
+    fn temperatures(&self) -> Vec<u16> {
+        let by_month = [
+            self.day_temperature_by_month,
+            self.night_temperature_by_month,
+        ];
+
+        by_month
+            .into_iter()
+            .flatten()
+            .flatten()
+            .chain(
+                self.day_length_scaling
+                    .as_ref()
+                    .map(|scaling| scaling.short_day_night_temperature),
+            )
+            .chain(self.warmth_limit.as_ref().map(|limit| limit.temperature))
+            .collect()
+    }
+}
+
+/// Night temperature interpolated by day length, between `short_day_night_temperature` on days
+/// shorter than `short_day` and the regular night temperature on days longer than `long_day`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DayLengthScaling {
+    pub short_day: SignedDuration,
+    pub long_day: SignedDuration,
+    pub short_day_night_temperature: u16,
+}
+
+impl DayLengthScaling {
+    fn night_temperature(&self, day_length: SignedDuration, night_temperature: u16) -> u16 {
+        let position = (day_length - self.short_day).as_secs_f64()
+            / (self.long_day - self.short_day).as_secs_f64();
+        let position = position.clamp(0.0, 1.0);
+
+        let short = self.short_day_night_temperature as f64;
+        (short + (night_temperature as f64 - short) * position).round() as u16
+    }
+}
+
+/// Minimum temperature from sunrise until a local clock time, relaxed over `ramp_duration`
+/// afterwards
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarmthLimit {
+    pub until: Time,
+    /// Temperature in Kelvin
+    pub temperature: u16,
+    #[serde(default = "default_warmth_limit_ramp")]
+    pub ramp_duration: SignedDuration,
+}
+
+fn default_warmth_limit_ramp() -> SignedDuration {
+    SignedDuration::from_hours(1)
+}
+
+/// Settings that replace the base configuration on matching days
+///
+/// A day matches if it is one of `weekdays`, inside one of `date_ranges` or listed in `dates_file`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// Name shown in logs and `status`
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_weekdays")]
+    pub weekdays: Vec<Weekday>,
+    #[serde(default)]
+    pub date_ranges: Vec<DateRange>,
+    /// File with one `YYYY-MM-DD` date per line, relative to the config file
+    pub dates_file: Option<PathBuf>,
+    #[serde(skip)]
+    dates: Vec<Date>,
+
+    pub day_temperature: Option<u16>,
+    pub night_temperature: Option<u16>,
+    pub transition: Option<Transition>,
+    pub keyframes: Option<Vec<Keyframe>>,
+    pub bedtime: Option<Bedtime>,
+    pub season: Option<Season>,
+    pub altitude_curve: Option<AltitudeCurve>,
+}
+
+impl Override {
+    pub fn matches(&self, date: Date) -> bool {
+        self.weekdays.contains(&date.weekday())
+            || self.date_ranges.iter().any(|range| range.contains(date))
+            || self.dates.contains(&date)
+    }
+
+    fn load_dates_file(&mut self, config_dir: &Path) -> color_eyre::Result<()> {
+        let Some(dates_file) = &self.dates_file else {
+            return Ok(());
+        };
+        let path = config_dir.join(dates_file);
+
+        let contents = fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+        for (index, line) in contents.lines().enumerate() {
+            // Allow comments and empty lines
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let date = line.parse().wrap_err_with(|| {
+                format!("Invalid date on line {} of {}", index + 1, path.display())
+            })?;
+            self.dates.push(date);
+        }
+
+        Ok(())
+    }
+}
+
+/// Inclusive range of dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DateRange {
+    pub from: Date,
+    pub to: Date,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: Date) -> bool {
+        (self.from..=self.to).contains(&date)
+    }
+}
+
+fn deserialize_weekdays<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Weekday>, D::Error> {
+    let names = Vec::<String>::deserialize(deserializer)?;
+
+    names
+        .iter()
+        .map(|name| match name.to_lowercase().as_str() {
+            "monday" | "mon" => Ok(Weekday::Monday),
+            "tuesday" | "tue" => Ok(Weekday::Tuesday),
+            "wednesday" | "wed" => Ok(Weekday::Wednesday),
+            "thursday" | "thu" => Ok(Weekday::Thursday),
+            "friday" | "fri" => Ok(Weekday::Friday),
+            "saturday" | "sat" => Ok(Weekday::Saturday),
+            "sunday" | "sun" => Ok(Weekday::Sunday),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Str(name),
+                &"a weekday like \"saturday\" or \"sat\"",
+            )),
+        })
+        .collect()
+}
+
+/// Color setting reached at a point in time relative to an anchor
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    pub anchor: Anchor,
+    #[serde(default)]
+    pub offset: SignedDuration,
+    /// Temperature in Kelvin
+    pub temperature: u16,
+    /// Brightness in the range `0.1..=1.0`
+    #[serde(default = "default_brightness")]
+    pub brightness: f64,
+}
+
+impl Keyframe {
+    pub fn new(anchor: Anchor, offset: SignedDuration, color: ColorSetting) -> Self {
+        Self {
+            anchor,
+            offset,
+            temperature: color.temperature,
+            brightness: color.brightness,
+        }
+    }
+
+    pub fn color(&self) -> ColorSetting {
+        ColorSetting {
+            temperature: self.temperature,
+            brightness: self.brightness,
+        }
+    }
+}
+
+fn default_brightness() -> f64 {
+    1.0
+}
+
+/// Event a keyframe is anchored to, either a solar event or a local clock time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Anchor {
+    Sun(SunEvent),
+    Time(Time),
+}
+
+impl FromStr for Anchor {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dawn" => Ok(Anchor::Sun(SunEvent::Dawn)),
+            "sunrise" => Ok(Anchor::Sun(SunEvent::Sunrise)),
+            "solar-noon" => Ok(Anchor::Sun(SunEvent::SolarNoon)),
+            "sunset" => Ok(Anchor::Sun(SunEvent::Sunset)),
+            "dusk" => Ok(Anchor::Sun(SunEvent::Dusk)),
+            time => time.parse().map(Anchor::Time).map_err(|_| {
+                eyre!(
+                    "Invalid anchor {time:?}, expected dawn, sunrise, solar-noon, sunset, dusk or a time like \"22:00\""
+                )
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for Anchor {
+    type Error = color_eyre::Report;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Anchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anchor::Sun(event) => write!(f, "{event}"),
+            Anchor::Time(time) => write!(f, "{}", time.strftime("%H:%M")),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("automatic-redshift").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+
+    fn parse_override(toml: &str) -> Override {
+        toml::from_str(&format!("name = \"test\"\n{toml}")).unwrap()
+    }
+
+    #[test]
+    fn override_matches_weekdays() {
+        let weekend = parse_override(r#"weekdays = ["saturday", "Sun"]"#);
+
+        // 2026-10-16 is a Friday
+        assert!(!weekend.matches(date(2026, 10, 16)));
+        assert!(weekend.matches(date(2026, 10, 17)));
+        assert!(weekend.matches(date(2026, 10, 18)));
+        assert!(!weekend.matches(date(2026, 10, 19)));
+
+        let error = toml::from_str::<Override>("name = \"test\"\nweekdays = [\"someday\"]");
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn override_date_ranges_include_both_ends() {
+        let holidays = parse_override(
+            r#"
+            date_ranges = [
+                { from = "2026-12-24", to = "2026-12-26" },
+                { from = "2026-12-31", to = "2027-01-01" },
+            ]
+            "#,
+        );
+
+        assert!(!holidays.matches(date(2026, 12, 23)));
+        assert!(holidays.matches(date(2026, 12, 24)));
+        assert!(holidays.matches(date(2026, 12, 25)));
+        assert!(holidays.matches(date(2026, 12, 26)));
+        assert!(!holidays.matches(date(2026, 12, 27)));
+        assert!(holidays.matches(date(2026, 12, 31)));
+        assert!(holidays.matches(date(2027, 1, 1)));
+        assert!(!holidays.matches(date(2027, 1, 2)));
+    }
+
+    #[test]
+    fn override_loads_dates_file() {
+        let dir = env::temp_dir().join(format!("automatic-redshift-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("holidays.txt"),
+            "# Public holidays\n2026-10-03\n\n2026-12-25  # Christmas\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.txt"), "2026-10-03\n2026-13-01\n").unwrap();
+
+        let mut holidays = parse_override(r#"dates_file = "holidays.txt""#);
+        assert!(!holidays.matches(date(2026, 10, 3)));
+        holidays.load_dates_file(&dir).unwrap();
+        assert!(holidays.matches(date(2026, 10, 3)));
+        assert!(!holidays.matches(date(2026, 10, 4)));
+        assert!(holidays.matches(date(2026, 12, 25)));
+
+        let mut broken = parse_override(r#"dates_file = "broken.txt""#);
+        let error = broken.load_dates_file(&dir).unwrap_err();
+        assert!(
+            error.to_string().starts_with("Invalid date on line 2 of"),
+            "{error}"
+        );
+
+        let mut missing = parse_override(r#"dates_file = "missing.txt""#);
+        assert!(missing.load_dates_file(&dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn day_length_scaling_interpolates_between_short_and_long_days() {
+        let scaling = DayLengthScaling {
+            short_day: SignedDuration::from_hours(8),
+            long_day: SignedDuration::from_hours(16),
+            short_day_night_temperature: 5000,
+        };
+        let at = |hours: f64| {
+            scaling.night_temperature(SignedDuration::from_secs_f64(hours * 3600.0), 3000)
+        };
+
+        assert_eq!(at(6.0), 5000);
+        assert_eq!(at(8.0), 5000);
+        assert_eq!(at(10.0), 4500);
+        assert_eq!(at(12.0), 4000);
+        assert_eq!(at(15.0), 3250);
+        assert_eq!(at(16.0), 3000);
+        assert_eq!(at(20.0), 3000);
+    }
+
+    #[test]
+    fn season_looks_up_temperatures_by_month() {
+        let config: Config = toml::from_str(
+            r#"
+            [season]
+            day_temperature_by_month = [5500, 5600, 5700, 5800, 5900, 6000, 6100, 6200, 6300, 6400, 6500, 6600]
+            night_temperature_by_month = [3100, 3200, 3300, 3400, 3500, 3600, 3700, 3800, 3900, 4000, 4100, 4200]
+            "#,
+        )
+        .unwrap();
+        let day = SignedDuration::from_hours(12);
+
+        let january = config.with_season(1, day);
+        assert_eq!(january.day_temperature, 5500);
+        assert_eq!(january.night_temperature, 3100);
+        let december = config.with_season(12, day);
+        assert_eq!(december.day_temperature, 6600);
+        assert_eq!(december.night_temperature, 4200);
+
+        // Day length scaling starts from the month's night temperature
+        let scaled: Config = toml::from_str(
+            r#"
+            night_temperature = 3000
+            [season]
+            night_temperature_by_month = [3100, 3200, 3300, 3400, 3500, 3600, 3700, 3800, 3900, 4000, 4100, 4200]
+            day_length_scaling = { short_day = "8h", long_day = "16h", short_day_night_temperature = 5000 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scaled.with_season(6, day).night_temperature, 4300);
+        assert_eq!(
+            scaled
+                .with_season(6, SignedDuration::from_hours(17))
+                .night_temperature,
+            3600
+        );
+        assert_eq!(
+            scaled
+                .with_season(1, SignedDuration::from_hours(7))
+                .night_temperature,
+            5000
+        );
+
+        // Without a season the config is unchanged
+        assert_eq!(Config::default().with_season(6, day), Config::default());
+    }
+
+    #[test]
+    fn debug_output_leaves_out_coordinates() {
+        let config: Config = toml::from_str(
+            r#"
+            [location.static]
+            latitude = 52.516312
+            longitude = 13.377541
+
+            [[places]]
+            name = "office"
+            latitude = 48.137154
+            longitude = 11.576124
+            ssids = ["office-wifi"]
+            "#,
+        )
+        .unwrap();
+        let debug = format!("{config:?}");
+
+        for coordinate in ["52.516312", "13.377541", "48.137154", "11.576124"] {
+            assert!(!debug.contains(coordinate), "{coordinate} in {debug}");
+        }
+        assert!(debug.contains("office-wifi"));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    color::ColorSetting,
+    config::Config,
+    location::Coordinates,
+    schedule::{SunEvent, calculate_sun, get_temperature},
+};
+use clap::ValueEnum;
+use jiff::{Zoned, civil::Date, tz::TimeZone};
+use serde::Serialize;
+use std::io::Write;
+
+/// Sun events the forecast lists for every day
+const EVENTS: [SunEvent; 4] = [
+    SunEvent::Dawn,
+    SunEvent::Sunrise,
+    SunEvent::Sunset,
+    SunEvent::Dusk,
+];
+
+/// Output format of the forecast
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+/// A sun event of the forecast and the color at that time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Row {
+    pub date: Date,
+    pub event: SunEvent,
+    #[serde(serialize_with = "serialize_zoned")]
+    pub time: Zoned,
+    pub temperature: u16,
+    pub brightness: f64,
+}
+
+/// Sun events and colors for `days` local days starting at `start`
+pub fn forecast(
+    start: Date,
+    days: u16,
+    coordinates: Coordinates,
+    config: &Config,
+    timezone: &TimeZone,
+) -> Result<Vec<Row>, jiff::Error> {
+    let mut rows = Vec::with_capacity(days as usize * EVENTS.len());
+
+    let mut date = start;
+    for _ in 0..days {
+        let noon = date.at(12, 0, 0, 0).to_zoned(timezone.clone())?;
+        let sun = calculate_sun(noon.timestamp(), coordinates);
+
+        for event in EVENTS {
+            let time = sun.time_of(event);
+            let color = get_temperature(time, coordinates, config, timezone)?;
+
+            rows.push(Row {
+                date,
+                event,
+                time: time.to_zoned(timezone.clone()),
+                temperature: color.temperature,
+                brightness: color.brightness,
+            });
+        }
+
+        date = date.tomorrow()?;
+    }
+
+    Ok(rows)
+}
+
+pub fn write(rows: &[Row], format: Format, mut out: impl Write) -> color_eyre::Result<()> {
+    match format {
+        Format::Text => {
+            let mut previous_date = None;
+            for row in rows {
+                if previous_date != Some(row.date) {
+                    writeln!(out, "{}", row.date.strftime("%a %Y-%m-%d"))?;
+                    previous_date = Some(row.date);
+                }
+
+                let color = ColorSetting {
+                    temperature: row.temperature,
+                    brightness: row.brightness,
+                };
+                writeln!(
+                    out,
+                    "  {:<8} {}  {color}",
+                    row.event.to_string(),
+                    row.time.strftime("%H:%M")
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, rows)?;
+            writeln!(out)?;
+        }
+        Format::Csv => {
+            writeln!(out, "date,event,time,temperature,brightness")?;
+            for row in rows {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    row.date,
+                    row.event,
+                    format_zoned(&row.time),
+                    row.temperature,
+                    row.brightness
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// RFC 3339 timestamp with the local offset
+fn format_zoned(zoned: &Zoned) -> String {
+    zoned.strftime("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
+fn serialize_zoned<S: serde::Serializer>(zoned: &Zoned, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_zoned(zoned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+
+    const TROMSO: Coordinates = Coordinates {
+        latitude: 69.65,
+        longitude: 18.96,
+        altitude: 0.0,
+    };
+
+    /// A day of polar night, with twilight but no sunrise, and one of midnight sun
+    fn polar_rows() -> Vec<Row> {
+        let oslo = TimeZone::get("Europe/Oslo").unwrap();
+        let config = Config::default();
+
+        [date(2026, 12, 21), date(2026, 6, 21)]
+            .into_iter()
+            .flat_map(|start| forecast(start, 1, TROMSO, &config, &oslo).unwrap())
+            .collect()
+    }
+
+    fn output(rows: &[Row], format: Format) -> String {
+        let mut out = Vec::new();
+        write(rows, format, &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lists_every_event_of_every_day() {
+        let berlin = TimeZone::get("Europe/Berlin").unwrap();
+        let coordinates = Coordinates {
+            latitude: 52.52,
+            longitude: 13.40,
+            altitude: 0.0,
+        };
+        let config = Config::default();
+
+        // Across the end of daylight saving time on October 25th
+        let rows = forecast(date(2026, 10, 18), 14, coordinates, &config, &berlin).unwrap();
+        assert_eq!(rows.len(), 14 * EVENTS.len());
+        for (day, rows) in rows.chunks(EVENTS.len()).enumerate() {
+            let expected = date(2026, 10, 18) + jiff::Span::new().days(day as i64);
+            for (row, event) in rows.iter().zip(EVENTS) {
+                assert_eq!(row.date, expected);
+                assert_eq!(row.event, event);
+                assert_eq!(row.time.date(), expected);
+            }
+        }
+        assert!(rows.windows(2).all(|pair| pair[0].time < pair[1].time));
+
+        assert!(
+            forecast(date(2026, 10, 18), 0, coordinates, &config, &berlin)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn writes_text() {
+        let expected = "\
+Mon 2026-12-21
+  dawn     09:31  4000 K
+  sunrise  11:42  6500 K
+  sunset   11:42  6500 K
+  dusk     13:53  4000 K
+Sun 2026-06-21
+  dawn     00:45  6500 K
+  sunrise  00:45  6500 K
+  sunset   00:46  6500 K
+  dusk     00:46  6500 K
+";
+        assert_eq!(output(&polar_rows(), Format::Text), expected);
+    }
+
+    #[test]
+    fn writes_json() {
+        let expected = r#"[
+  {
+    "date": "2026-12-21",
+    "event": "dawn",
+    "time": "2026-12-21T09:31:13+01:00",
+    "temperature": 4000,
+    "brightness": 1.0
+  },
+  {
+    "date": "2026-12-21",
+    "event": "sunrise",
+    "time": "2026-12-21T11:42:12+01:00",
+    "temperature": 6500,
+    "brightness": 1.0
+  },
+  {
+    "date": "2026-12-21",
+    "event": "sunset",
+    "time": "2026-12-21T11:42:12+01:00",
+    "temperature": 6500,
+    "brightness": 1.0
+  },
+  {
+    "date": "2026-12-21",
+    "event": "dusk",
+    "time": "2026-12-21T13:53:11+01:00",
+    "temperature": 4000,
+    "brightness": 1.0
+  }
+]
+"#;
+        assert_eq!(output(&polar_rows()[..4], Format::Json), expected);
+    }
+
+    #[test]
+    fn writes_csv() {
+        let expected = "\
+date,event,time,temperature,brightness
+2026-12-21,dawn,2026-12-21T09:31:13+01:00,4000,1
+2026-12-21,sunrise,2026-12-21T11:42:12+01:00,6500,1
+2026-12-21,sunset,2026-12-21T11:42:12+01:00,6500,1
+2026-12-21,dusk,2026-12-21T13:53:11+01:00,4000,1
+2026-06-21,dawn,2026-06-21T00:45:51+02:00,6500,1
+2026-06-21,sunrise,2026-06-21T00:45:51+02:00,6500,1
+2026-06-21,sunset,2026-06-22T00:46:04+02:00,6500,1
+2026-06-21,dusk,2026-06-22T00:46:04+02:00,6500,1
+";
+        assert_eq!(output(&polar_rows(), Format::Csv), expected);
+    }
+}
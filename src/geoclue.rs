@@ -1,55 +1,219 @@
+use crate::{
+    backoff::Backoff,
+    config::{GeoClue, GeoClueAgent},
+    location::{
+        Coordinates, Fix, LocationEvent, LocationProvider, ProviderState, Source, connect_then,
+    },
+};
 use async_stream::stream;
-use futures::{Stream, StreamExt};
-use zbus::{Connection, proxy};
-use zvariant::ObjectPath;
+use futures::{Stream, StreamExt, stream::LocalBoxStream};
+use jiff::Timestamp;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::{select, time::sleep};
+use zbus::{
+    Connection,
+    fdo::{DBusProxy, NameOwnerChanged},
+    interface,
+    names::BusName,
+    proxy,
+};
+use zvariant::{ObjectPath, OwnedObjectPath};
 
-#[derive(Debug, Clone, Copy)]
-pub struct Coordinates {
-    pub latitude: f64,
-    pub longitude: f64,
+const GEOCLUE_SERVICE: &str = "org.freedesktop.GeoClue2";
+const AGENT_PATH: &str = "/org/freedesktop/GeoClue2/Agent";
+/// Desktop ID the client and the agent identify with
+const DESKTOP_ID: &str = "automatic-redshift";
+
+/// Location provider backed by the GeoClue service
+#[derive(Debug, Clone)]
+pub struct GeoClueProvider {
+    settings: GeoClue,
+}
+
+impl GeoClueProvider {
+    pub fn new(settings: GeoClue) -> Self {
+        Self { settings }
+    }
+}
+
+impl LocationProvider for GeoClueProvider {
+    fn source(&self) -> Source {
+        Source::GeoClue
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        connect_then(Source::GeoClue, location_stream(self.settings.clone()))
+    }
 }
 
-pub async fn location_coordinates_stream()
--> color_eyre::Result<impl Stream<Item = color_eyre::Result<Coordinates>>> {
+/// Locations reported by GeoClue, recreating the client whenever the service restarts
+pub async fn location_stream(
+    settings: GeoClue,
+) -> color_eyre::Result<impl Stream<Item = LocationEvent>> {
     let connection = Connection::system().await?;
 
-    let geoclue_manager = ManagerProxy::new(&connection).await?;
-    let geoclue_client = geoclue_manager.get_client().await?;
+    if let Some(agent) = &settings.agent {
+        connection
+            .object_server()
+            .at(AGENT_PATH, Agent::new(agent.clone()))
+            .await?;
+    }
 
-    geoclue_client.set_desktop_id("automatic-redshift").await?;
-    geoclue_client.set_distance_threshold(10000).await?; // meters
-    geoclue_client
-        .set_requested_accuracy_level(AccuracyLevel::City as u32)
+    let dbus = DBusProxy::new(&connection).await?;
+    let mut owner_changed = dbus
+        .receive_name_owner_changed_with_args(&[(0, GEOCLUE_SERVICE)])
         .await?;
 
-    let mut location_updated = geoclue_client.receive_location_updated().await?;
-    geoclue_client.start().await?;
+    let location_stream = stream! {
+        let mut backoff = Backoff::default();
+
+        loop {
+            yield LocationEvent::State(ProviderState::Connecting);
 
-    let coordinates_stream = stream! {
-        while let Some(signal) = location_updated.next().await {
-            let args = signal.args()?;
+            match start_client(&connection, &settings).await {
+                Ok((client, mut location_updated)) => {
+                    info!("GeoClue client started");
+                    // Starting the client may have activated the service and changed the owner
+                    let owner = name_owner(&dbus).await;
+                    yield LocationEvent::State(ProviderState::Running);
+                    backoff.reset();
 
-                let location = LocationProxy::builder(&connection)
-                    .path(args.new())?
-                    .build()
-                    .await?;
+                    // GeoClue may already have a fix, which isn't signalled again
+                    match client.location().await {
+                        Ok(path) if path.as_str() != "/" => {
+                            match read_fix(&connection, path.into()).await {
+                                Ok(fix) => yield LocationEvent::Fix(fix),
+                                Err(err) => warn!("Failed to read location from GeoClue: {err}"),
+                            }
+                        }
+                        Ok(_) => (),
+                        Err(err) => warn!("Failed to read location from GeoClue: {err}"),
+                    }
 
-                let latitude = location.latitude().await?;
-                let longitude = location.longitude().await?;
+                    loop {
+                        let signal = select! {
+                            signal = location_updated.next() => signal,
+                            Some(changed) = owner_changed.next() => {
+                                if new_owner(&changed) == owner {
+                                    continue;
+                                }
+                                // The client belongs to the previous owner of the name
+                                None
+                            }
+                        };
+                        let Some(signal) = signal else {
+                            break;
+                        };
 
-               yield Ok::<Coordinates, color_eyre::Report>(Coordinates {
-                    latitude,
-                    longitude,
-                });
+                        let fix = match signal.args() {
+                            Ok(args) => read_fix(&connection, args.new().to_owned()).await,
+                            Err(err) => Err(err.into()),
+                        };
+                        match fix {
+                            Ok(fix) => yield LocationEvent::Fix(fix),
+                            Err(err) => warn!("Failed to read location from GeoClue: {err}"),
+                        }
+                    }
+
+                    warn!("Lost connection to GeoClue, restarting the client");
+                    // Fails if the service is gone, the client is dropped either way
+                    let _ = client.stop().await;
+                }
+                Err(err) => {
+                    warn!("Failed to start GeoClue client: {err}");
+                }
+            }
+
+            yield LocationEvent::State(ProviderState::Disconnected);
+
+            let delay = backoff.next_delay();
+            debug!("Retrying GeoClue in {delay:?}");
+            // Retry right away when the service comes back
+            select! {
+                _ = sleep(delay) => (),
+                Some(_) = owner_changed.next() => (),
+            }
         }
     };
 
-    Ok(coordinates_stream)
+    Ok(location_stream)
+}
+
+async fn name_owner(dbus: &DBusProxy<'_>) -> Option<String> {
+    let name = BusName::try_from(GEOCLUE_SERVICE).ok()?;
+    let owner = dbus.get_name_owner(name).await.ok()?;
+
+    Some(owner.to_string())
+}
+
+fn new_owner(changed: &NameOwnerChanged) -> Option<String> {
+    let args = changed.args().ok()?;
+
+    args.new_owner().as_ref().map(|owner| owner.to_string())
+}
+
+async fn start_client<'a>(
+    connection: &'a Connection,
+    settings: &GeoClue,
+) -> zbus::Result<(ClientProxy<'a>, LocationUpdatedStream)> {
+    let geoclue_manager = ManagerProxy::new(connection).await?;
+
+    // Needs to be repeated whenever GeoClue restarts
+    if settings.agent.is_some()
+        && let Err(err) = geoclue_manager.add_agent(DESKTOP_ID).await
+    {
+        warn!("Failed to register as GeoClue agent: {err}");
+    }
+
+    let geoclue_client = geoclue_manager.get_client().await?;
+
+    geoclue_client.set_desktop_id(DESKTOP_ID).await?;
+    geoclue_client
+        .set_distance_threshold(settings.distance_threshold)
+        .await?;
+    geoclue_client
+        .set_time_threshold(settings.time_threshold_secs())
+        .await?;
+    geoclue_client
+        .set_requested_accuracy_level(settings.accuracy_level as u32)
+        .await?;
+
+    let location_updated = geoclue_client.receive_location_updated().await?;
+    geoclue_client.start().await?;
+
+    Ok((geoclue_client, location_updated))
+}
+
+async fn read_fix(connection: &Connection, path: ObjectPath<'_>) -> color_eyre::Result<Fix> {
+    let location = LocationProxy::builder(connection)
+        .path(path)?
+        .build()
+        .await?;
+
+    let latitude = location.latitude().await?;
+    let longitude = location.longitude().await?;
+    let altitude = location.altitude().await?;
+    let accuracy = location.accuracy().await?;
+    let (seconds, microseconds) = location.timestamp().await?;
+
+    Ok(Fix {
+        coordinates: Coordinates {
+            latitude,
+            longitude,
+            // GeoClue reports the lowest double if the altitude is unknown
+            altitude: if altitude == f64::MIN { 0.0 } else { altitude },
+        },
+        source: Source::GeoClue,
+        accuracy: Some(accuracy),
+        timestamp: Timestamp::new(seconds as i64, microseconds as i32 * 1000)?,
+    })
 }
 
-#[allow(dead_code)]
-enum AccuracyLevel {
-    None = 0,
+/// Precision GeoClue determines the location with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccuracyLevel {
     Country = 1,
     City = 4,
     Neighborhood = 5,
@@ -66,6 +230,52 @@ trait Manager {
     /// GetClient method
     #[zbus(object = "Client")]
     fn get_client(&self);
+
+    /// AddAgent method
+    fn add_agent(&self, id: &str) -> zbus::Result<()>;
+}
+
+/// Authorizes apps to access the location on behalf of the user, instead of the agent of a
+/// desktop environment
+struct Agent {
+    policy: GeoClueAgent,
+}
+
+impl Agent {
+    fn new(policy: GeoClueAgent) -> Self {
+        Self { policy }
+    }
+}
+
+#[interface(name = "org.freedesktop.GeoClue2.Agent")]
+impl Agent {
+    /// AuthorizeApp method
+    fn authorize_app(&self, desktop_id: &str, req_accuracy_level: u32) -> (bool, u32) {
+        let (authorized, accuracy_level) = authorize(&self.policy, desktop_id, req_accuracy_level);
+
+        if authorized {
+            info!("Authorized {desktop_id} to access the location");
+        } else {
+            warn!("Denied {desktop_id} access to the location");
+        }
+
+        (authorized, accuracy_level)
+    }
+
+    /// MaxAccuracyLevel property
+    #[zbus(property)]
+    fn max_accuracy_level(&self) -> u32 {
+        self.policy.max_accuracy_level as u32
+    }
+}
+
+/// Whether `policy` lets the app access the location, and the accuracy level it gets for the one
+/// it requested
+fn authorize(policy: &GeoClueAgent, desktop_id: &str, req_accuracy_level: u32) -> (bool, u32) {
+    let authorized = policy.allowed_apps.iter().any(|app| app == desktop_id);
+    let accuracy_level = req_accuracy_level.min(policy.max_accuracy_level as u32);
+
+    (authorized, accuracy_level)
 }
 
 #[proxy(
@@ -91,9 +301,17 @@ trait Client {
     #[zbus(property)]
     fn set_distance_threshold(&self, meters: u32) -> zbus::Result<()>;
 
+    /// TimeThreshold property
+    #[zbus(property)]
+    fn set_time_threshold(&self, seconds: u32) -> zbus::Result<()>;
+
     /// RequestedAccuracyLevel property
     #[zbus(property)]
     fn set_requested_accuracy_level(&self, level: u32) -> zbus::Result<()>;
+
+    /// Location property
+    #[zbus(property)]
+    fn location(&self) -> zbus::Result<OwnedObjectPath>;
 }
 
 #[proxy(
@@ -108,4 +326,60 @@ trait Location {
     /// Longitude property
     #[zbus(property)]
     fn longitude(&self) -> zbus::Result<f64>;
+
+    /// Altitude property
+    #[zbus(property)]
+    fn altitude(&self) -> zbus::Result<f64>;
+
+    /// Accuracy property
+    #[zbus(property)]
+    fn accuracy(&self) -> zbus::Result<f64>;
+
+    /// Timestamp property
+    #[zbus(property)]
+    fn timestamp(&self) -> zbus::Result<(u64, u64)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_apps: &[&str], max_accuracy_level: AccuracyLevel) -> GeoClueAgent {
+        GeoClueAgent {
+            allowed_apps: allowed_apps.iter().map(|&app| app.to_owned()).collect(),
+            max_accuracy_level,
+        }
+    }
+
+    #[test]
+    fn authorizes_allowed_apps() {
+        let policy = policy(
+            &["automatic-redshift", "org.gnome.Weather"],
+            AccuracyLevel::Exact,
+        );
+
+        assert_eq!(authorize(&policy, "automatic-redshift", 8), (true, 8));
+        assert_eq!(authorize(&policy, "org.gnome.Weather", 4), (true, 4));
+    }
+
+    #[test]
+    fn denies_other_apps() {
+        let policy = policy(&["automatic-redshift"], AccuracyLevel::Exact);
+
+        assert!(!authorize(&policy, "org.gnome.Maps", 8).0);
+        assert!(!authorize(&policy, "automatic-redshift.desktop", 8).0);
+        assert!(!authorize(&policy, "", 8).0);
+        assert!(!authorize(&GeoClueAgent::default(), "firefox", 4).0);
+    }
+
+    #[test]
+    fn caps_the_accuracy_level() {
+        let policy = policy(&["automatic-redshift"], AccuracyLevel::City);
+
+        assert_eq!(authorize(&policy, "automatic-redshift", 8), (true, 4));
+        assert_eq!(authorize(&policy, "automatic-redshift", 6), (true, 4));
+        assert_eq!(authorize(&policy, "automatic-redshift", 4), (true, 4));
+        // Less precise requests are granted as is
+        assert_eq!(authorize(&policy, "automatic-redshift", 1), (true, 1));
+    }
 }
@@ -0,0 +1,321 @@
+use crate::{
+    backoff::Backoff,
+    config::Gpsd,
+    location::{Coordinates, Fix, LocationEvent, LocationProvider, ProviderState, Source},
+};
+use async_stream::stream;
+use futures::{Stream, StreamExt, stream::LocalBoxStream};
+use jiff::Timestamp;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::TcpStream,
+    time::sleep,
+};
+
+/// Asks gpsd to stream reports as JSON, one object per line
+const WATCH: &[u8] = b"?WATCH={\"enable\":true,\"json\":true};\n";
+
+/// Location provider backed by gpsd
+#[derive(Debug, Clone)]
+pub struct GpsdProvider {
+    settings: Gpsd,
+}
+
+impl GpsdProvider {
+    pub fn new(settings: Gpsd) -> Self {
+        Self { settings }
+    }
+}
+
+impl LocationProvider for GpsdProvider {
+    fn source(&self) -> Source {
+        Source::Gpsd
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        location_stream(self.settings.clone()).boxed_local()
+    }
+}
+
+/// Locations from the TPV reports of gpsd, reconnecting whenever the connection is lost
+pub fn location_stream(settings: Gpsd) -> impl Stream<Item = LocationEvent> {
+    stream! {
+        let mut backoff = Backoff::default();
+
+        loop {
+            yield LocationEvent::State(ProviderState::Connecting);
+
+            match connect(&settings.address).await {
+                Ok(mut lines) => {
+                    info!("Connected to gpsd at {}", settings.address);
+                    yield LocationEvent::State(ProviderState::Running);
+                    backoff.reset();
+                    // gpsd reports about once a second, mostly the same position
+                    let mut reported: Option<Fix> = None;
+
+                    loop {
+                        let line = match lines.next_line().await {
+                            Ok(Some(line)) => line,
+                            Ok(None) => break,
+                            Err(err) => {
+                                warn!("Failed to read from gpsd: {err}");
+                                break;
+                            }
+                        };
+
+                        match parse_fix(&line) {
+                            Ok(Some(fix)) if is_news(reported.as_ref(), &fix, settings.distance_threshold) => {
+                                reported = Some(fix);
+                                yield LocationEvent::Fix(fix);
+                            }
+                            Ok(_) => (),
+                            Err(err) => warn!("Invalid report from gpsd: {err}"),
+                        }
+                    }
+
+                    warn!("Lost connection to gpsd");
+                }
+                Err(err) => warn!("Failed to connect to gpsd at {}: {err}", settings.address),
+            }
+
+            yield LocationEvent::State(ProviderState::Disconnected);
+
+            let delay = backoff.next_delay();
+            debug!("Reconnecting to gpsd in {delay:?}");
+            sleep(delay).await;
+        }
+    }
+}
+
+async fn connect(address: &str) -> std::io::Result<Lines<BufReader<TcpStream>>> {
+    let mut stream = TcpStream::connect(address).await?;
+    stream.write_all(WATCH).await?;
+
+    Ok(BufReader::new(stream).lines())
+}
+
+/// Whether `fix` moved at least `threshold` meters from the last reported one, or is at least
+/// twice as accurate
+fn is_news(reported: Option<&Fix>, fix: &Fix, threshold: u32) -> bool {
+    let Some(reported) = reported else {
+        return true;
+    };
+
+    let moved = reported.coordinates.distance(&fix.coordinates) >= f64::from(threshold);
+    let more_accurate = match (reported.accuracy, fix.accuracy) {
+        (Some(reported), Some(accuracy)) => accuracy * 2.0 <= reported,
+        (None, Some(_)) => true,
+        (_, None) => false,
+    };
+
+    moved || more_accurate
+}
+
+/// Reports sent by gpsd, only time-position-velocity reports carry a location
+#[derive(Debug, Deserialize)]
+#[serde(tag = "class")]
+enum Report {
+    #[serde(rename = "TPV")]
+    Tpv(Tpv),
+    #[serde(other)]
+    Other,
+}
+
+/// See <https://gpsd.gitlab.io/gpsd/gpsd_json.html#_tpv>
+#[derive(Debug, Deserialize)]
+struct Tpv {
+    /// 0 and 1 mean no fix, 2 a 2D and 3 a 3D fix
+    #[serde(default)]
+    mode: u8,
+    time: Option<Timestamp>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// Altitude above mean sea level, `alt` in gpsd before 3.20
+    #[serde(rename = "altMSL")]
+    alt_msl: Option<f64>,
+    alt: Option<f64>,
+    /// Estimated horizontal position error in meters
+    eph: Option<f64>,
+    /// Estimated longitude and latitude errors in meters
+    epx: Option<f64>,
+    epy: Option<f64>,
+}
+
+/// The location in a line sent by gpsd, if it is a report with a fix
+fn parse_fix(line: &str) -> serde_json::Result<Option<Fix>> {
+    let Report::Tpv(tpv) = serde_json::from_str(line)? else {
+        return Ok(None);
+    };
+
+    let (Some(latitude), Some(longitude)) = (tpv.lat, tpv.lon) else {
+        return Ok(None);
+    };
+    if tpv.mode < 2 {
+        return Ok(None);
+    }
+
+    let altitude = if tpv.mode >= 3 {
+        tpv.alt_msl.or(tpv.alt).unwrap_or(0.0)
+    } else {
+        0.0
+    };
+    let accuracy = tpv.eph.or_else(|| Some(tpv.epx?.max(tpv.epy?)));
+
+    Ok(Some(Fix {
+        coordinates: Coordinates {
+            latitude,
+            longitude,
+            altitude,
+        },
+        source: Source::Gpsd,
+        accuracy,
+        timestamp: tpv.time.unwrap_or_else(Timestamp::now),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+        time::timeout,
+    };
+
+    const VERSION: &str =
+        r#"{"class":"VERSION","release":"3.25","rev":"3.25","proto_major":3,"proto_minor":15}"#;
+    const NO_FIX: &str = r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":1}"#;
+    const FIX_3D: &str = r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":3,"time":"2026-10-18T09:30:00.000Z","lat":47.376887,"lon":8.541694,"alt":460.2,"altMSL":408.0,"eph":12.5}"#;
+    const FIX_2D: &str = r#"{"class":"TPV","device":"/dev/ttyUSB0","mode":2,"lat":52.520008,"lon":13.404954,"altMSL":34.0,"epx":8.0,"epy":15.0}"#;
+
+    #[test]
+    fn parses_reports() {
+        assert!(parse_fix(VERSION).unwrap().is_none());
+        assert!(parse_fix(NO_FIX).unwrap().is_none());
+        assert!(parse_fix("not json").is_err());
+
+        let fix = parse_fix(FIX_3D).unwrap().unwrap();
+        assert_eq!(fix.coordinates.latitude, 47.376887);
+        assert_eq!(fix.coordinates.longitude, 8.541694);
+        assert_eq!(fix.coordinates.altitude, 408.0);
+        assert_eq!(fix.accuracy, Some(12.5));
+        assert_eq!(
+            fix.timestamp,
+            "2026-10-18T09:30:00Z".parse::<Timestamp>().unwrap()
+        );
+
+        // Without a 3D fix the altitude is meaningless
+        let fix = parse_fix(FIX_2D).unwrap().unwrap();
+        assert_eq!(fix.coordinates.altitude, 0.0);
+        assert_eq!(fix.accuracy, Some(15.0));
+    }
+
+    #[test]
+    fn reports_only_movement_and_better_accuracy() {
+        let fix = parse_fix(FIX_3D).unwrap().unwrap();
+        assert!(is_news(None, &fix, 1000));
+        assert!(!is_news(Some(&fix), &fix, 1000));
+
+        // About 560 m north
+        let nearby = Fix {
+            coordinates: Coordinates {
+                latitude: fix.coordinates.latitude + 0.005,
+                ..fix.coordinates
+            },
+            ..fix
+        };
+        assert!(!is_news(Some(&fix), &nearby, 1000));
+        assert!(is_news(Some(&fix), &nearby, 500));
+        assert!(is_news(Some(&fix), &fix, 0));
+
+        let precise = Fix {
+            accuracy: Some(5.0),
+            ..fix
+        };
+        let less_precise = Fix {
+            accuracy: Some(8.0),
+            ..fix
+        };
+        assert!(is_news(Some(&fix), &precise, 1000));
+        assert!(!is_news(Some(&fix), &less_precise, 1000));
+    }
+
+    #[tokio::test]
+    async fn streams_fixes_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let settings = Gpsd {
+            address: listener.local_addr().unwrap().to_string(),
+            ..Gpsd::default()
+        };
+
+        let stream = location_stream(settings);
+        futures::pin_mut!(stream);
+
+        let server = async {
+            // The first gpsd sends the same fix twice and exits
+            serve(&listener, &[VERSION, NO_FIX, FIX_3D, FIX_3D]).await;
+            // Its replacement is reached after the backoff
+            serve(&listener, &[VERSION, FIX_2D]).await
+        };
+
+        let client = async {
+            let mut fixes = Vec::new();
+            let mut states = Vec::new();
+
+            while fixes.len() < 2 {
+                match stream.next().await.unwrap() {
+                    LocationEvent::Fix(fix) => fixes.push(fix),
+                    LocationEvent::State(state) => states.push(state),
+                }
+            }
+
+            (fixes, states)
+        };
+
+        let (connection, (fixes, states)) = timeout(Duration::from_secs(10), async {
+            tokio::join!(server, client)
+        })
+        .await
+        .expect("gpsd provider did not deliver two fixes");
+
+        // Keep the last connection open until the client is done
+        drop(connection);
+
+        assert_eq!(fixes[0].coordinates.latitude, 47.376887);
+        assert_eq!(fixes[1].coordinates.latitude, 52.520008);
+        assert_eq!(
+            states,
+            [
+                ProviderState::Connecting,
+                ProviderState::Running,
+                ProviderState::Disconnected,
+                ProviderState::Connecting,
+                ProviderState::Running,
+            ]
+        );
+    }
+
+    /// Accept a client, check it enables watching and send it `reports`
+    async fn serve(listener: &TcpListener, reports: &[&str]) -> TcpStream {
+        let (connection, _) = listener.accept().await.unwrap();
+        let mut connection = BufReader::new(connection);
+
+        let mut watch = String::new();
+        connection.read_line(&mut watch).await.unwrap();
+        assert!(watch.starts_with("?WATCH="), "unexpected command {watch:?}");
+        assert!(watch.contains(r#""json":true"#));
+
+        for report in reports {
+            connection
+                .write_all(format!("{report}\r\n").as_bytes())
+                .await
+                .unwrap();
+        }
+
+        connection.into_inner()
+    }
+}
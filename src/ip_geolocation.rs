@@ -0,0 +1,391 @@
+use crate::{
+    backoff::Backoff,
+    config::IpGeolocation,
+    location::{
+        Coordinates, Fix, LocationEvent, LocationProvider, ProviderState, Source, cache_dir,
+    },
+};
+use async_stream::stream;
+use color_eyre::eyre::{OptionExt, bail, eyre};
+use futures::{Stream, StreamExt, stream::LocalBoxStream};
+use jiff::Timestamp;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Location provider looking up the public IP address
+#[derive(Debug, Clone)]
+pub struct IpGeolocationProvider {
+    settings: IpGeolocation,
+    /// Decimal places the result is rounded to before it is cached
+    decimals: u8,
+}
+
+impl IpGeolocationProvider {
+    pub fn new(settings: IpGeolocation, decimals: u8) -> Self {
+        Self { settings, decimals }
+    }
+}
+
+impl LocationProvider for IpGeolocationProvider {
+    fn source(&self) -> Source {
+        Source::IpGeolocation
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        let cache_path = cache_dir().map(|dir| dir.join("ip-geolocation.json"));
+
+        location_stream(self.settings.clone(), self.decimals, cache_path).boxed_local()
+    }
+}
+
+/// Location looked up from the public IP address, queried again once the cached result is
+/// older than the TTL
+///
+/// Latitude and longitude are rounded to `decimals` decimal places, so the precise result isn't
+/// written to the cache.
+pub fn location_stream(
+    settings: IpGeolocation,
+    decimals: u8,
+    cache_path: Option<PathBuf>,
+) -> impl Stream<Item = LocationEvent> {
+    stream! {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("automatic-redshift/", env!("CARGO_PKG_VERSION")))
+            .build();
+        let client = match client {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to create HTTP client: {err}");
+                yield LocationEvent::State(ProviderState::Disconnected);
+                return;
+            }
+        };
+        let mut backoff = Backoff::default();
+
+        yield LocationEvent::State(ProviderState::Connecting);
+
+        loop {
+            let cached = cache_path
+                .as_ref()
+                .and_then(|path| CachedLocation::read(path, &settings.url));
+
+            let fix = match cached {
+                Some(cached) if !cached.is_expired(&settings) => {
+                    debug!("Using cached location from {}", cached.timestamp);
+                    Ok(cached.fix())
+                }
+                _ => query(&client, &settings).await.map(|fix| Fix {
+                    coordinates: fix.coordinates.rounded(decimals),
+                    ..fix
+                }).inspect(|&fix| {
+                    if let Some(path) = &cache_path
+                        && let Err(err) = CachedLocation::new(&settings.url, fix).write(path)
+                    {
+                        warn!("Failed to cache location: {err}");
+                    }
+                }),
+            };
+
+            match fix {
+                Ok(fix) => {
+                    yield LocationEvent::State(ProviderState::Running);
+                    yield LocationEvent::Fix(fix);
+                    backoff.reset();
+
+                    let expires = fix.timestamp.saturating_add(settings.ttl).unwrap_or(Timestamp::MAX);
+                    let remaining = expires.duration_since(Timestamp::now());
+                    sleep(remaining.try_into().unwrap_or(Duration::ZERO)).await;
+                }
+                Err(err) => {
+                    warn!("Failed to look up location at {}: {err}", settings.url);
+                    yield LocationEvent::State(ProviderState::Disconnected);
+
+                    let delay = backoff.next_delay();
+                    debug!("Retrying IP geolocation in {delay:?}");
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+async fn query(client: &reqwest::Client, settings: &IpGeolocation) -> color_eyre::Result<Fix> {
+    let response: Value = client
+        .get(&settings.url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let latitude = field(&response, &settings.latitude_field, 0)?;
+    let longitude = field(&response, &settings.longitude_field, 1)?;
+    info!("Looked up location at {}", settings.url);
+
+    Ok(Fix {
+        coordinates: Coordinates {
+            latitude,
+            longitude,
+            altitude: 0.0,
+        },
+        source: Source::IpGeolocation,
+        accuracy: None,
+        timestamp: Timestamp::now(),
+    })
+}
+
+/// Number at the dot-separated `path`
+///
+/// Strings are parsed as well. If the string holds both coordinates separated by a comma, like
+/// `loc` of ipinfo.io, `index` picks one of them.
+fn field(response: &Value, path: &str, index: usize) -> color_eyre::Result<f64> {
+    let pointer = format!("/{}", path.replace('.', "/"));
+    let value = response
+        .pointer(&pointer)
+        .ok_or_else(|| eyre!("Response has no field {path}"))?;
+
+    match value {
+        Value::Number(number) => number.as_f64().ok_or_eyre("Number out of range"),
+        Value::String(string) => {
+            let parts: Vec<&str> = string.split(',').collect();
+            let part = if parts.len() == 1 {
+                parts[0]
+            } else {
+                parts
+                    .get(index)
+                    .ok_or_else(|| eyre!("Field {path} has no part {index}"))?
+            };
+
+            Ok(part.trim().parse()?)
+        }
+        _ => bail!("Field {path} is not a number"),
+    }
+}
+
+/// Last looked up location, kept across restarts so the endpoint isn't queried more often than
+/// the TTL allows
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedLocation {
+    /// Endpoint the location came from, a different one invalidates the cache
+    url: String,
+    latitude: f64,
+    longitude: f64,
+    timestamp: Timestamp,
+}
+
+impl CachedLocation {
+    fn new(url: &str, fix: Fix) -> Self {
+        Self {
+            url: url.to_owned(),
+            latitude: fix.coordinates.latitude,
+            longitude: fix.coordinates.longitude,
+            timestamp: fix.timestamp,
+        }
+    }
+
+    fn read(path: &Path, url: &str) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+        let cached: Self = serde_json::from_slice(&contents).ok()?;
+
+        (cached.url == url).then_some(cached)
+    }
+
+    fn write(&self, path: &Path) -> color_eyre::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    fn is_expired(&self, settings: &IpGeolocation) -> bool {
+        let age = Timestamp::now().duration_since(self.timestamp);
+
+        age.is_negative() || age >= settings.ttl
+    }
+
+    fn fix(&self) -> Fix {
+        Fix {
+            coordinates: Coordinates {
+                latitude: self.latitude,
+                longitude: self.longitude,
+                altitude: 0.0,
+            },
+            source: Source::IpGeolocation,
+            accuracy: None,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::SignedDuration;
+    use serde_json::json;
+    use std::{
+        env,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::timeout,
+    };
+
+    #[test]
+    fn reads_fields() {
+        let ip_api = json!({ "status": "success", "lat": 52.5196, "lon": 13.4069 });
+        assert_eq!(field(&ip_api, "lat", 0).unwrap(), 52.5196);
+        assert_eq!(field(&ip_api, "lon", 1).unwrap(), 13.4069);
+
+        let ipinfo = json!({ "ip": "192.0.2.1", "loc": "47.3769,8.5417" });
+        assert_eq!(field(&ipinfo, "loc", 0).unwrap(), 47.3769);
+        assert_eq!(field(&ipinfo, "loc", 1).unwrap(), 8.5417);
+
+        let nested = json!({ "location": { "latitude": "-33.8688", "longitude": 151.2093 } });
+        assert_eq!(field(&nested, "location.latitude", 0).unwrap(), -33.8688);
+        assert_eq!(field(&nested, "location.longitude", 1).unwrap(), 151.2093);
+
+        assert!(field(&nested, "location.altitude", 0).is_err());
+        assert!(field(&nested, "location", 0).is_err());
+        assert!(field(&json!({ "lat": "north" }), "lat", 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn queries_endpoint_and_caches_result() {
+        let (url, requests) = serve("200 OK", r#"{"loc":"47.3769,8.5417"}"#).await;
+        let cache_path = temp_cache_path("cached");
+        let settings = IpGeolocation {
+            url,
+            latitude_field: "loc".to_owned(),
+            longitude_field: "loc".to_owned(),
+            ..IpGeolocation::default()
+        };
+
+        let fix = first_fix(settings.clone(), Some(cache_path.clone())).await;
+        assert_eq!(fix.coordinates.latitude, 47.38);
+        assert_eq!(fix.coordinates.longitude, 8.54);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Only the rounded location is written to disk
+        let written = CachedLocation::read(&cache_path, &settings.url).unwrap();
+        assert_eq!((written.latitude, written.longitude), (47.38, 8.54));
+
+        // A restart within the TTL uses the cache
+        let cached = first_fix(settings.clone(), Some(cache_path.clone())).await;
+        assert_eq!(cached.coordinates.latitude, 47.38);
+        assert_eq!(cached.timestamp, fix.timestamp);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Once it expired the endpoint is queried again
+        let expired = IpGeolocation {
+            ttl: SignedDuration::from_nanos(1),
+            ..settings
+        };
+        first_fix(expired, Some(cache_path.clone())).await;
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        fs::remove_file(cache_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_failed_lookups() {
+        let (url, requests) = serve("503 Service Unavailable", "{}").await;
+        let settings = IpGeolocation {
+            url,
+            ..IpGeolocation::default()
+        };
+
+        let stream = location_stream(settings, 2, None);
+        futures::pin_mut!(stream);
+
+        let states: Vec<_> = timeout(Duration::from_secs(10), stream.take(2).collect())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            states[..],
+            [
+                LocationEvent::State(ProviderState::Connecting),
+                LocationEvent::State(ProviderState::Disconnected)
+            ]
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    async fn first_fix(settings: IpGeolocation, cache_path: Option<PathBuf>) -> Fix {
+        let stream = location_stream(settings, 2, cache_path);
+        futures::pin_mut!(stream);
+
+        let fix = async {
+            loop {
+                if let LocationEvent::Fix(fix) = stream.next().await.unwrap() {
+                    return fix;
+                }
+            }
+        };
+
+        timeout(Duration::from_secs(10), fix).await.unwrap()
+    }
+
+    /// Minimal HTTP server answering every request with `body`, returns its URL and the number
+    /// of requests it received
+    async fn serve(status: &'static str, body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/json", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut connection, _) = listener.accept().await.unwrap();
+
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = connection.read(&mut buffer).await.unwrap();
+                    assert_ne!(read, 0, "connection closed before the end of the request");
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                assert!(request.starts_with(b"GET /json HTTP/1.1\r\n"));
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                connection.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn temp_cache_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "automatic-redshift-test-{}-{name}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+}
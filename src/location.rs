@@ -0,0 +1,755 @@
+use crate::{
+    config::{Config, StaticLocation},
+    geoclue::GeoClueProvider,
+    gpsd::GpsdProvider,
+    ip_geolocation::IpGeolocationProvider,
+    places::PlacesProvider,
+    timezone::TimeZoneProvider,
+};
+use async_stream::stream;
+use futures::{
+    Stream, StreamExt,
+    future::poll_fn,
+    stream::{self, LocalBoxStream},
+};
+use jiff::{SignedDuration, Timestamp};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    task::Poll,
+    time::Duration,
+};
+use tokio::{
+    select,
+    time::{Instant, sleep_until},
+};
+
+/// Minimum time between updates of the cached location
+const CACHE_INTERVAL: SignedDuration = SignedDuration::from_mins(1);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    pub altitude: f64,
+}
+
+impl Coordinates {
+    /// Latitude and longitude rounded to `decimals` decimal places
+    pub fn rounded(self, decimals: u8) -> Self {
+        let factor = 10f64.powi(decimals.into());
+
+        Self {
+            latitude: (self.latitude * factor).round() / factor,
+            longitude: (self.longitude * factor).round() / factor,
+            altitude: self.altitude,
+        }
+    }
+
+    /// Great-circle distance to `other` in meters
+    pub fn distance(&self, other: &Self) -> f64 {
+        const EARTH_RADIUS: f64 = 6_371_000.0;
+
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let delta_lat = lat2 - lat1;
+        let delta_lon = (other.longitude - self.longitude).to_radians();
+        let a = (delta_lat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// A location reported by a provider
+#[derive(Debug, Clone, Copy)]
+pub struct Fix {
+    pub coordinates: Coordinates,
+    pub source: Source,
+    /// Radius in meters the actual location is within, if known
+    pub accuracy: Option<f64>,
+    /// When the location was determined
+    pub timestamp: Timestamp,
+}
+
+/// Kinds of location providers, in the order they can be listed in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    #[serde(rename = "geoclue")]
+    GeoClue,
+    Gpsd,
+    IpGeolocation,
+    Places,
+    /// The last location any of the providers above reported
+    Cached,
+    /// The principal city of the system time zone
+    TimeZone,
+    /// Fixed coordinates from the configuration
+    Static,
+}
+
+impl Source {
+    /// Whether the location is measured and worth remembering for the cached provider
+    fn is_measured(self) -> bool {
+        !matches!(self, Source::Cached | Source::TimeZone | Source::Static)
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::GeoClue => write!(f, "geoclue"),
+            Source::Gpsd => write!(f, "gpsd"),
+            Source::IpGeolocation => write!(f, "ip-geolocation"),
+            Source::Places => write!(f, "places"),
+            Source::Cached => write!(f, "cached"),
+            Source::TimeZone => write!(f, "time-zone"),
+            Source::Static => write!(f, "static"),
+        }
+    }
+}
+
+/// Whether the location provider is delivering locations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderState {
+    /// Connecting to the provider
+    #[default]
+    Connecting,
+    Running,
+    /// The provider went away or could not be reached, retrying with backoff
+    Disconnected,
+}
+
+impl Display for ProviderState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderState::Connecting => write!(f, "connecting"),
+            ProviderState::Running => write!(f, "running"),
+            ProviderState::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LocationEvent {
+    Fix(Fix),
+    State(ProviderState),
+}
+
+/// A source of locations
+pub trait LocationProvider {
+    fn source(&self) -> Source;
+
+    /// Start the provider, a provider that gives up reports itself as disconnected and ends the
+    /// stream
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent>;
+}
+
+/// Stream of a provider that has to connect before delivering locations, failing to connect
+/// ends it as disconnected
+pub fn connect_then<S>(
+    source: Source,
+    connect: impl Future<Output = color_eyre::Result<S>> + 'static,
+) -> LocalBoxStream<'static, LocationEvent>
+where
+    S: Stream<Item = LocationEvent> + 'static,
+{
+    stream::once(connect)
+        .flat_map(move |result| match result {
+            Ok(locations) => locations.boxed_local(),
+            Err(err) => {
+                warn!("Failed to start {source} location provider: {err}");
+                stream::iter([LocationEvent::State(ProviderState::Disconnected)]).boxed_local()
+            }
+        })
+        .boxed_local()
+}
+
+/// Report a single location once and keep it
+pub(crate) fn constant(fix: Fix) -> LocalBoxStream<'static, LocationEvent> {
+    stream::iter([
+        LocationEvent::State(ProviderState::Running),
+        LocationEvent::Fix(fix),
+    ])
+    .chain(stream::pending())
+    .boxed_local()
+}
+
+/// The last measured location, remembered across restarts
+#[derive(Debug, Clone, Default)]
+pub struct CachedProvider {
+    path: Option<PathBuf>,
+}
+
+impl CachedProvider {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+}
+
+impl LocationProvider for CachedProvider {
+    fn source(&self) -> Source {
+        Source::Cached
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        match self.path.as_deref().and_then(LastLocation::read) {
+            Some(last) => constant(Fix {
+                coordinates: last.coordinates(),
+                source: Source::Cached,
+                accuracy: last.accuracy,
+                timestamp: last.timestamp,
+            }),
+            None => {
+                debug!("No cached location");
+                stream::iter([LocationEvent::State(ProviderState::Disconnected)]).boxed_local()
+            }
+        }
+    }
+}
+
+/// Coordinates from the configuration
+#[derive(Debug, Clone, Copy)]
+pub struct StaticProvider {
+    location: StaticLocation,
+}
+
+impl StaticProvider {
+    pub fn new(location: StaticLocation) -> Self {
+        Self { location }
+    }
+}
+
+impl LocationProvider for StaticProvider {
+    fn source(&self) -> Source {
+        Source::Static
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        constant(Fix {
+            coordinates: self.location.coordinates(),
+            source: Source::Static,
+            accuracy: None,
+            timestamp: Timestamp::now(),
+        })
+    }
+}
+
+/// Locations from the configured providers in order of priority
+///
+/// A provider is used as long as it delivers locations. When it disconnects, or no provider has
+/// delivered a location within the timeout, the next one is started. Lower priority providers
+/// are stopped again as soon as a higher priority one recovers.
+pub fn location_stream(config: &Config) -> LocalBoxStream<'static, LocationEvent> {
+    let providers = config
+        .location_providers()
+        .into_iter()
+        .map(|source| provider(source, config))
+        .collect();
+    let timeout = Duration::try_from(config.location.timeout).unwrap_or(Duration::ZERO);
+
+    provider_chain(
+        providers,
+        timeout,
+        config.privacy.coordinate_decimals,
+        cache_path(),
+    )
+}
+
+/// Locations of `providers` in order of priority, see [`location_stream`]
+///
+/// Measured locations are written to `cache_path` for the cached provider.
+fn provider_chain(
+    providers: Vec<Box<dyn LocationProvider>>,
+    timeout: Duration,
+    decimals: u8,
+    cache_path: Option<PathBuf>,
+) -> LocalBoxStream<'static, LocationEvent> {
+    stream! {
+        let count = providers.len();
+        let mut streams: Vec<Option<LocalBoxStream<'static, LocationEvent>>> =
+            (0..count).map(|_| None).collect();
+        let mut states = vec![ProviderState::Connecting; count];
+        let mut last_fixes: Vec<Option<Fix>> = vec![None; count];
+        // Index of the provider whose locations are used
+        let mut active: Option<usize> = None;
+        let mut started = 0;
+        let mut deadline = Instant::now();
+        let mut last_cached: Option<Timestamp> = None;
+
+        yield LocationEvent::State(ProviderState::Connecting);
+
+        loop {
+            // Start the next provider while none delivers locations
+            if active.is_none() && started < count && Instant::now() >= deadline {
+                let next = &providers[started];
+                if started == 0 {
+                    info!("Starting {} location provider", next.source());
+                } else {
+                    info!("No location yet, falling back to {} location provider", next.source());
+                }
+                streams[started] = Some(next.locations());
+                started += 1;
+                deadline = Instant::now() + timeout;
+            }
+
+            let can_fall_back = active.is_none() && started < count;
+            let next = select! {
+                event = next_event(&mut streams) => Some(event),
+                _ = sleep_until(deadline), if can_fall_back => None,
+            };
+            // Timed out, the next provider is started above
+            let Some((index, event)) = next else {
+                continue;
+            };
+
+            match event {
+                Some(LocationEvent::Fix(fix)) => {
+                    // Nothing downstream, not even the location cache, sees the precise location.
+                    // Providers keeping their own cache round before writing it.
+                    let fix = Fix {
+                        coordinates: fix.coordinates.rounded(decimals),
+                        ..fix
+                    };
+                    states[index] = ProviderState::Running;
+                    last_fixes[index] = Some(fix);
+
+                    if active.is_some_and(|active| active < index) {
+                        continue;
+                    }
+                    if active != Some(index) {
+                        info!("Using location from {}", providers[index].source());
+                        active = Some(index);
+                        yield LocationEvent::State(ProviderState::Running);
+
+                        // Stop the fallbacks
+                        for fallback in index + 1..started {
+                            streams[fallback] = None;
+                            states[fallback] = ProviderState::Connecting;
+                            last_fixes[fallback] = None;
+                        }
+                        started = index + 1;
+                    }
+
+                    let cache_due = last_cached.is_none_or(|cached| {
+                        fix.timestamp.duration_since(cached) >= CACHE_INTERVAL
+                    });
+                    if let Some(path) = &cache_path
+                        && fix.source.is_measured()
+                        && cache_due
+                    {
+                        LastLocation::new(fix).write(path);
+                        last_cached = Some(fix.timestamp);
+                    }
+                    yield LocationEvent::Fix(fix);
+                }
+                // The stream ended when the provider gave up
+                None | Some(LocationEvent::State(ProviderState::Disconnected)) => {
+                    states[index] = ProviderState::Disconnected;
+
+                    if active == Some(index) {
+                        warn!("Lost location from {}", providers[index].source());
+                        active = None;
+                        // A fallback that still runs takes over
+                        let fallback = (index + 1..started).find_map(|fallback| {
+                            let fix = last_fixes[fallback]?;
+                            (states[fallback] == ProviderState::Running).then_some((fallback, fix))
+                        });
+                        if let Some((fallback, fix)) = fallback {
+                            info!("Using location from {}", providers[fallback].source());
+                            active = Some(fallback);
+                            yield LocationEvent::Fix(fix);
+                        }
+                    }
+
+                    if active.is_none() {
+                        if started < count {
+                            // Don't wait for the timeout of a provider that failed
+                            deadline = Instant::now();
+                        } else if states.iter().all(|&state| state == ProviderState::Disconnected) {
+                            warn!("No location provider is available");
+                            yield LocationEvent::State(ProviderState::Disconnected);
+                        }
+                    }
+                }
+                Some(LocationEvent::State(state)) => states[index] = state,
+            }
+        }
+    }
+    .boxed_local()
+}
+
+fn provider(source: Source, config: &Config) -> Box<dyn LocationProvider> {
+    match source {
+        Source::GeoClue => Box::new(GeoClueProvider::new(config.geoclue.clone())),
+        Source::Gpsd => Box::new(GpsdProvider::new(config.gpsd.clone().unwrap_or_default())),
+        Source::IpGeolocation => Box::new(IpGeolocationProvider::new(
+            config.ip_geolocation.clone().unwrap_or_default(),
+            config.privacy.coordinate_decimals,
+        )),
+        Source::Places => Box::new(PlacesProvider::new(config.places.clone())),
+        Source::Cached => Box::new(CachedProvider::new(cache_path())),
+        Source::TimeZone => Box::new(TimeZoneProvider),
+        Source::Static => {
+            Box::new(StaticProvider::new(config.location.static_location.expect(
+                "static provider without coordinates should be rejected by validation",
+            )))
+        }
+    }
+}
+
+/// Next event of any running provider together with its index, `None` when its stream ended
+async fn next_event(
+    streams: &mut [Option<LocalBoxStream<'static, LocationEvent>>],
+) -> (usize, Option<LocationEvent>) {
+    poll_fn(|cx| {
+        for (index, slot) in streams.iter_mut().enumerate() {
+            if let Some(stream) = slot
+                && let Poll::Ready(event) = stream.poll_next_unpin(cx)
+            {
+                if event.is_none() {
+                    *slot = None;
+                }
+                return Poll::Ready((index, event));
+            }
+        }
+
+        Poll::Pending
+    })
+    .await
+}
+
+/// The last measured location, written to the cache directory
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct LastLocation {
+    latitude: f64,
+    longitude: f64,
+    altitude: f64,
+    accuracy: Option<f64>,
+    timestamp: Timestamp,
+}
+
+impl LastLocation {
+    fn new(fix: Fix) -> Self {
+        Self {
+            latitude: fix.coordinates.latitude,
+            longitude: fix.coordinates.longitude,
+            altitude: fix.coordinates.altitude,
+            accuracy: fix.accuracy,
+            timestamp: fix.timestamp,
+        }
+    }
+
+    fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: self.altitude,
+        }
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let contents = fs::read(path).ok()?;
+
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Atomically replace the cached location, failures only affect the cached provider
+    fn write(&self, path: &Path) {
+        let result = (|| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            let temp_path = path.with_extension("json.tmp");
+            fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+            fs::rename(&temp_path, path)?;
+
+            color_eyre::Result::<()>::Ok(())
+        })();
+
+        if let Err(err) = result {
+            warn!("Failed to cache location: {err}");
+        }
+    }
+}
+
+/// Where the last measured location is cached
+fn cache_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("location.json"))
+}
+
+/// `$CACHE_DIRECTORY` when run as a systemd service, otherwise below `$XDG_CACHE_HOME`
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("CACHE_DIRECTORY")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .map(|dir| dir.join("automatic-redshift"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{sleep, timeout};
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Provider whose locations come from a function, restarted on every call
+    struct FakeProvider {
+        source: Source,
+        locations: Box<dyn Fn() -> LocalBoxStream<'static, LocationEvent>>,
+    }
+
+    impl FakeProvider {
+        fn boxed(
+            source: Source,
+            locations: impl Fn() -> LocalBoxStream<'static, LocationEvent> + 'static,
+        ) -> Box<dyn LocationProvider> {
+            Box::new(Self {
+                source,
+                locations: Box::new(locations),
+            })
+        }
+    }
+
+    impl LocationProvider for FakeProvider {
+        fn source(&self) -> Source {
+            self.source
+        }
+
+        fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+            (self.locations)()
+        }
+    }
+
+    fn fix(source: Source, latitude: f64) -> Fix {
+        Fix {
+            coordinates: Coordinates {
+                latitude,
+                longitude: 13.4,
+                altitude: 0.0,
+            },
+            source,
+            accuracy: None,
+            timestamp: Timestamp::now(),
+        }
+    }
+
+    fn never() -> LocalBoxStream<'static, LocationEvent> {
+        stream::pending().boxed_local()
+    }
+
+    fn disconnected() -> LocalBoxStream<'static, LocationEvent> {
+        stream::iter([LocationEvent::State(ProviderState::Disconnected)]).boxed_local()
+    }
+
+    async fn next(stream: &mut LocalBoxStream<'static, LocationEvent>) -> LocationEvent {
+        timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("no location event")
+            .expect("location stream ended")
+    }
+
+    async fn next_state(stream: &mut LocalBoxStream<'static, LocationEvent>) -> ProviderState {
+        match next(stream).await {
+            LocationEvent::State(state) => state,
+            event => panic!("expected a state, got {event:?}"),
+        }
+    }
+
+    async fn next_fix(stream: &mut LocalBoxStream<'static, LocationEvent>) -> Fix {
+        match next(stream).await {
+            LocationEvent::Fix(fix) => fix,
+            event => panic!("expected a fix, got {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_after_timeout() {
+        let providers = vec![
+            FakeProvider::boxed(Source::GeoClue, never),
+            FakeProvider::boxed(Source::Static, || constant(fix(Source::Static, 52.520_008))),
+        ];
+        let start = Instant::now();
+        let mut stream = provider_chain(providers, TIMEOUT, 2, None);
+
+        assert_eq!(next_state(&mut stream).await, ProviderState::Connecting);
+        assert_eq!(next_state(&mut stream).await, ProviderState::Running);
+        let fix = next_fix(&mut stream).await;
+        assert!(start.elapsed() >= TIMEOUT);
+        assert_eq!(fix.source, Source::Static);
+        assert_eq!(fix.coordinates.latitude, 52.52);
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_disconnect() {
+        let providers = vec![
+            FakeProvider::boxed(Source::GeoClue, || {
+                stream! {
+                    yield LocationEvent::State(ProviderState::Running);
+                    yield LocationEvent::Fix(fix(Source::GeoClue, 1.0));
+                    sleep(Duration::from_millis(20)).await;
+                    yield LocationEvent::State(ProviderState::Disconnected);
+                }
+                .boxed_local()
+            }),
+            FakeProvider::boxed(Source::Gpsd, disconnected),
+            FakeProvider::boxed(Source::Static, || constant(fix(Source::Static, 2.0))),
+        ];
+        let start = Instant::now();
+        let mut stream = provider_chain(providers, Duration::from_secs(60), 4, None);
+
+        assert_eq!(next_state(&mut stream).await, ProviderState::Connecting);
+        assert_eq!(next_state(&mut stream).await, ProviderState::Running);
+        assert_eq!(next_fix(&mut stream).await.source, Source::GeoClue);
+
+        // Neither the lost nor the failing provider holds up the next one
+        assert_eq!(next_state(&mut stream).await, ProviderState::Running);
+        assert_eq!(next_fix(&mut stream).await.source, Source::Static);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn higher_priority_provider_takes_over() {
+        let providers = vec![
+            FakeProvider::boxed(Source::GeoClue, || {
+                stream! {
+                    sleep(TIMEOUT * 3).await;
+                    yield LocationEvent::State(ProviderState::Running);
+                    yield LocationEvent::Fix(fix(Source::GeoClue, 1.0));
+                }
+                .chain(stream::pending())
+                .boxed_local()
+            }),
+            FakeProvider::boxed(Source::IpGeolocation, || {
+                stream! {
+                    yield LocationEvent::State(ProviderState::Running);
+                    loop {
+                        yield LocationEvent::Fix(fix(Source::IpGeolocation, 2.0));
+                        sleep(Duration::from_millis(20)).await;
+                    }
+                }
+                .boxed_local()
+            }),
+        ];
+        let mut stream = provider_chain(providers, TIMEOUT, 4, None);
+
+        assert_eq!(next_state(&mut stream).await, ProviderState::Connecting);
+        assert_eq!(next_state(&mut stream).await, ProviderState::Running);
+        assert_eq!(next_fix(&mut stream).await.source, Source::IpGeolocation);
+
+        let fix = loop {
+            match next(&mut stream).await {
+                LocationEvent::Fix(fix) if fix.source == Source::IpGeolocation => continue,
+                LocationEvent::State(ProviderState::Running) => break next_fix(&mut stream).await,
+                event => panic!("unexpected {event:?}"),
+            }
+        };
+        assert_eq!(fix.source, Source::GeoClue);
+
+        // The fallback was stopped
+        let event = timeout(TIMEOUT, stream.next()).await;
+        assert!(event.is_err(), "{event:?}");
+    }
+
+    #[tokio::test]
+    async fn reports_disconnected_when_all_providers_are() {
+        let providers = vec![
+            FakeProvider::boxed(Source::GeoClue, disconnected),
+            FakeProvider::boxed(Source::Gpsd, || stream::empty().boxed_local()),
+        ];
+        let mut stream = provider_chain(providers, TIMEOUT, 4, None);
+
+        assert_eq!(next_state(&mut stream).await, ProviderState::Connecting);
+        assert_eq!(next_state(&mut stream).await, ProviderState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn cached_provider_reports_last_measured_location() {
+        let path = env::temp_dir().join(format!(
+            "automatic-redshift-test-{}-location.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let mut cached = CachedProvider::new(Some(path.clone())).locations();
+        assert_eq!(next_state(&mut cached).await, ProviderState::Disconnected);
+
+        // Locations from the configuration aren't remembered
+        let providers = vec![FakeProvider::boxed(Source::Static, || {
+            constant(fix(Source::Static, 48.0))
+        })];
+        let mut stream = provider_chain(providers, TIMEOUT, 2, Some(path.clone()));
+        next_state(&mut stream).await;
+        next_state(&mut stream).await;
+        next_fix(&mut stream).await;
+        assert!(!path.exists());
+
+        // Measured ones are, rounded
+        let providers = vec![FakeProvider::boxed(Source::Gpsd, || {
+            constant(fix(Source::Gpsd, 52.516_31))
+        })];
+        let mut stream = provider_chain(providers, TIMEOUT, 2, Some(path.clone()));
+        next_state(&mut stream).await;
+        next_state(&mut stream).await;
+        next_fix(&mut stream).await;
+
+        let mut cached = CachedProvider::new(Some(path.clone())).locations();
+        assert_eq!(next_state(&mut cached).await, ProviderState::Running);
+        let fix = next_fix(&mut cached).await;
+        assert_eq!(fix.source, Source::Cached);
+        assert_eq!(fix.coordinates.latitude, 52.52);
+        assert_eq!(fix.coordinates.longitude, 13.4);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rounds_coordinates() {
+        let coordinates = Coordinates {
+            latitude: 52.516_31,
+            longitude: -13.377_54,
+            altitude: 34.5,
+        };
+
+        let rounded = coordinates.rounded(2);
+        assert_eq!((rounded.latitude, rounded.longitude), (52.52, -13.38));
+        assert_eq!(rounded.altitude, 34.5);
+        let rounded = coordinates.rounded(0);
+        assert_eq!((rounded.latitude, rounded.longitude), (53.0, -13.0));
+        let rounded = coordinates.rounded(5);
+        assert_eq!(
+            (rounded.latitude, rounded.longitude),
+            (52.516_31, -13.377_54)
+        );
+    }
+
+    #[test]
+    fn measures_great_circle_distance() {
+        let at = |latitude, longitude| Coordinates {
+            latitude,
+            longitude,
+            altitude: 0.0,
+        };
+        let berlin = at(52.52, 13.405);
+        let paris = at(48.8566, 2.3522);
+
+        assert_eq!(berlin.distance(&berlin), 0.0);
+        assert!((berlin.distance(&paris) - 877_463.0).abs() < 1.0);
+        assert_eq!(berlin.distance(&paris), paris.distance(&berlin));
+        // About 1 km per 0.009° of latitude, half the circumference to the antipode
+        assert!((at(0.0, 0.0).distance(&at(0.009, 0.0)) - 1000.75).abs() < 0.01);
+        assert!((at(0.0, 0.0).distance(&at(0.0, 180.0)) - 20_015_086.8).abs() < 1.0);
+    }
+}
@@ -1,80 +1,137 @@
+use clap::{Args, Parser, Subcommand};
+use clock::{Clock, SystemClock, VirtualClock};
+use color::ColorSetting;
+use color_eyre::eyre::bail;
+use config::Config;
 use env_logger::Env;
-use futures::{StreamExt, pin_mut};
-use geoclue::{Coordinates, location_coordinates_stream};
-use jiff::{Timestamp, tz::TimeZone};
-use log::{debug, info};
-use std::fmt::Display;
-use sun::{SunPhase, time_at_phase};
+use futures::StreamExt;
+use jiff::{SignedDuration, Timestamp, civil::DateTime, tz::TimeZone};
+use location::{Coordinates, LocationEvent, ProviderState, Source, location_stream};
+use log::{debug, info, warn};
+use scheduler::Scheduler;
+use status::Status;
+use std::{io, path::PathBuf};
 use tokio::{
     select,
     time::{Duration, sleep},
 };
 use wayland::Wayland;
 
+mod backoff;
+mod clock;
 mod color;
+mod config;
+mod forecast;
 mod geoclue;
+mod gpsd;
+mod ip_geolocation;
+mod location;
+mod places;
+mod ramp;
+mod schedule;
+mod scheduler;
+mod solar;
+mod status;
+mod timezone;
+mod transition;
 mod wayland;
 
-const HIGH_TEMP: u16 = 6500;
-const LOW_TEMP: u16 = 4000;
+/// How often the temperature is updated
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60);
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Sun {
-    dawn: Timestamp,
-    sunrise: Timestamp,
-    sunset: Timestamp,
-    dusk: Timestamp,
+/// Automatically adjust screen color temperature based on location and time of day
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the configuration file
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn interpolate_temperature(
-    now: Timestamp,
-    start: Timestamp,
-    stop: Timestamp,
-    temp_start: u16,
-    temp_stop: u16,
-) -> u16 {
-    if start == stop {
-        return temp_stop;
-    }
-    let time_pos = (now - start).get_seconds() as f64 / (stop - start).get_seconds() as f64;
-    let time_pos = time_pos.clamp(0.0, 1.0);
-    let temp_pos = (temp_stop as i32 - temp_start as i32) as f64 * time_pos;
-    (temp_start as f64 + temp_pos) as u16
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the daemon, the default
+    Run,
+    /// Show the state of the running daemon
+    Status,
+    /// Print sun events and the scheduled temperature for the coming days
+    Forecast {
+        /// Number of days, starting today
+        #[arg(long, default_value_t = 7)]
+        days: u16,
+
+        #[command(flatten)]
+        location: LocationArgs,
+
+        #[arg(long, value_enum, default_value_t)]
+        format: forecast::Format,
+    },
+    /// Run the schedule on a virtual clock, printing the temperature timeline
+    Simulate {
+        /// Local date and time to start at, like 2026-12-21T00:00, defaults to now
+        #[arg(long)]
+        start: Option<DateTime>,
+
+        /// Simulated time span
+        #[arg(long, default_value = "24h")]
+        duration: SignedDuration,
+
+        /// Simulated seconds per real second, the default runs a day in a minute
+        #[arg(long, default_value_t = 1440.0)]
+        speed: f64,
+
+        /// Apply the temperature to the displays instead of printing it
+        #[arg(long)]
+        apply: bool,
+
+        #[command(flatten)]
+        location: LocationArgs,
+    },
+    /// Show the outputs of the compositor, to match them in the configuration
+    ListOutputs,
 }
 
-fn calculate_sun(now: Timestamp, latitude: f64, longitude: f64) -> Result<Sun, jiff::Error> {
-    let now_ms = now.as_millisecond();
-
-    // Try to calculate sun times
-    let dawn_ms = time_at_phase(now_ms, SunPhase::Dawn, latitude, longitude, 0.0);
-    let sunrise_ms = time_at_phase(now_ms, SunPhase::Sunrise, latitude, longitude, 0.0);
-    let sunset_ms = time_at_phase(now_ms, SunPhase::Sunset, latitude, longitude, 0.0);
-    let dusk_ms = time_at_phase(now_ms, SunPhase::Dusk, latitude, longitude, 0.0);
-
-    let dawn = Timestamp::from_millisecond(dawn_ms)?;
-    let sunrise = Timestamp::from_millisecond(sunrise_ms)?;
-    let sunset = Timestamp::from_millisecond(sunset_ms)?;
-    let dusk = Timestamp::from_millisecond(dusk_ms)?;
-
-    Ok(Sun {
-        dawn,
-        sunrise,
-        sunset,
-        dusk,
-    })
+/// Fixed location instead of the one reported by the location provider
+#[derive(Debug, Args)]
+struct LocationArgs {
+    /// Latitude in degrees, north positive
+    #[arg(long, requires = "longitude", allow_negative_numbers = true)]
+    latitude: Option<f64>,
+
+    /// Longitude in degrees, east positive
+    #[arg(long, requires = "latitude", allow_negative_numbers = true)]
+    longitude: Option<f64>,
+
+    /// Altitude in metres above sea level
+    #[arg(long, requires = "latitude", default_value_t = 0.0)]
+    altitude: f64,
 }
 
-fn get_temperature(now: Timestamp, sun: Sun) -> u16 {
-    if now < sun.dawn {
-        LOW_TEMP
-    } else if now < sun.sunrise {
-        interpolate_temperature(now, sun.dawn, sun.sunrise, LOW_TEMP, HIGH_TEMP)
-    } else if now < sun.sunset {
-        HIGH_TEMP
-    } else if now < sun.dusk {
-        interpolate_temperature(now, sun.sunset, sun.dusk, HIGH_TEMP, LOW_TEMP)
-    } else {
-        LOW_TEMP
+impl LocationArgs {
+    /// The given location, or the first one reported by the location provider
+    async fn coordinates(&self, config: &Config) -> color_eyre::Result<Coordinates> {
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            return Ok(Coordinates {
+                latitude,
+                longitude,
+                altitude: self.altitude,
+            });
+        }
+
+        let mut location_stream = location_stream(config);
+
+        while let Some(event) = location_stream.next().await {
+            match event {
+                LocationEvent::Fix(fix) => return Ok(fix.coordinates),
+                LocationEvent::State(ProviderState::Disconnected) => break,
+                LocationEvent::State(_) => (),
+            }
+        }
+
+        bail!("No location available, pass --latitude and --longitude instead")
     }
 }
 
@@ -85,70 +142,209 @@ async fn main() -> color_eyre::Result<()> {
     let env = Env::default().filter_or("RUST_LOG", "info");
     env_logger::init_from_env(env);
 
-    let mut wayland = Wayland::new()?;
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            let config = Config::load(cli.config.as_deref())?;
+            debug!("Config: {config:?}");
+
+            run(config, SystemClock).await
+        }
+        Command::Status => {
+            println!("{}", Status::read()?);
+            Ok(())
+        }
+        Command::Forecast {
+            days,
+            location,
+            format,
+        } => {
+            let config = Config::load(cli.config.as_deref())?;
+            let coordinates = location.coordinates(&config).await?;
+
+            let timezone = TimeZone::system();
+            let today = SystemClock.now().to_zoned(timezone.clone()).date();
+            let rows = forecast::forecast(today, days, coordinates, &config, &timezone)?;
+
+            forecast::write(&rows, format, io::stdout().lock())
+        }
+        Command::Simulate {
+            start,
+            duration,
+            speed,
+            apply,
+            location,
+        } => {
+            if !(speed.is_finite() && speed > 0.0) {
+                bail!("Speed must be positive, got {speed}");
+            }
+            if duration.is_negative() {
+                bail!("Duration must not be negative, got {duration:#}");
+            }
+
+            let config = Config::load(cli.config.as_deref())?;
+            let coordinates = location.coordinates(&config).await?;
+
+            let start = match start {
+                Some(start) => start.to_zoned(TimeZone::system())?.timestamp(),
+                None => SystemClock.now(),
+            };
+            let end = start.checked_add(duration)?;
+            let wayland = if apply {
+                Some(Wayland::new(config.outputs.clone()))
+            } else {
+                None
+            };
 
-    let mut temp = 6500;
+            simulate(
+                config,
+                coordinates,
+                VirtualClock::new(start, speed),
+                end,
+                wayland,
+            )
+            .await
+        }
+        Command::ListOutputs => {
+            let outputs = wayland::list_outputs()?;
+            if outputs.is_empty() {
+                println!("No outputs");
+            }
+            for (index, output) in outputs.iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                println!("{output}");
+            }
 
-    let location_coordinates_stream = location_coordinates_stream().await?;
-    pin_mut!(location_coordinates_stream);
+            Ok(())
+        }
+    }
+}
 
+async fn run(config: Config, clock: impl Clock) -> color_eyre::Result<()> {
+    let mut wayland = Wayland::new(config.outputs.clone());
+
+    let mut color = ColorSetting::new(6500);
+
+    let mut location_stream = location_stream(&config);
+    let log_coordinates = config.privacy.log_coordinates;
+
+    let mut scheduler = Scheduler::new(config, clock, TimeZone::system());
     let mut coordinates = None;
-    let mut sun = None;
+    let mut location_source: Option<Source> = None;
+    let mut provider_state = ProviderState::default();
 
     loop {
         select! {
-            _ = wayland.poll() => (),
-            Some(new_coordinates) = location_coordinates_stream.next() => {
-                let new_coordinates = new_coordinates?;
-                info!("Latitude: {}, Longitude: {}", new_coordinates.latitude, new_coordinates.longitude);
-                coordinates = Some(new_coordinates);
+            errors = wayland.poll() => {
+                for err in errors {
+                    warn!("{err}");
+                }
+            }
+            Some(event) = location_stream.next() => match event {
+                LocationEvent::Fix(fix) => {
+                    let new_coordinates = fix.coordinates;
+                    let accuracy = fix.accuracy.map_or("unknown".to_owned(), |meters| format!("{meters} m"));
+                    if log_coordinates {
+                        info!(
+                            "Latitude: {}, Longitude: {}, Altitude: {} m, Accuracy: {accuracy}, Source: {}",
+                            new_coordinates.latitude,
+                            new_coordinates.longitude,
+                            new_coordinates.altitude,
+                            fix.source,
+                        );
+                    } else {
+                        info!("Location updated, Accuracy: {accuracy}, Source: {}", fix.source);
+                    }
+                    debug!("Location determined at {}", fix.timestamp);
+                    coordinates = Some(new_coordinates);
+                    location_source = Some(fix.source);
+                }
+                LocationEvent::State(state) => {
+                    debug!("Location provider is {state}");
+                    provider_state = state;
+                }
             },
-            _ = sleep(Duration::from_secs(60)) => (), // Update temperature every minute
+            _ = sleep(scheduler.clock().real_duration(UPDATE_INTERVAL)) => (),
         };
 
-        let Some(Coordinates {
-            latitude,
-            longitude,
-        }) = coordinates
-        else {
+        let Some(coordinates) = coordinates else {
             continue;
         };
 
-        let now = Timestamp::now();
-        debug!("Current time: {}", time_of(now));
+        let (now, new_color) = scheduler.update(coordinates)?;
+
+        // Also retries outputs that failed to update
+        wayland.set_temperature(new_color);
+        if new_color != color {
+            color = new_color;
+            info!("Updated temperature to {color}");
+        } else {
+            debug!("Temperature unchanged at {color}");
+        }
 
-        let new_sun = calculate_sun(now, latitude, longitude)?;
+        let active_override = scheduler.active_override().map(str::to_owned);
+        if let Err(err) = Status::new(
+            now,
+            color,
+            active_override,
+            provider_state,
+            location_source,
+            wayland.conflicts(),
+        )
+        .write()
+        {
+            warn!("Failed to write status: {err}");
+        }
+    }
+}
 
-        if Some(new_sun) != sun {
-            sun = Some(new_sun);
+/// Run the schedule from `start` to `end` on a virtual clock, either on the displays or printing
+/// every change of the color
+async fn simulate(
+    config: Config,
+    coordinates: Coordinates,
+    clock: VirtualClock,
+    end: Timestamp,
+    mut wayland: Option<Wayland>,
+) -> color_eyre::Result<()> {
+    let mut scheduler = Scheduler::new(config, clock, TimeZone::system());
+    let mut color = None;
 
-            info!(
-                "Dawn: {}, Sunrise: {}, Sunset: {}, Dusk: {}",
-                time_of(new_sun.dawn),
-                time_of(new_sun.sunrise),
-                time_of(new_sun.sunset),
-                time_of(new_sun.dusk)
-            );
+    loop {
+        let (now, new_color) = scheduler.update(coordinates)?;
+        if now > end {
+            return Ok(());
         }
 
-        let new_temp = get_temperature(now, new_sun);
+        if color != Some(new_color) {
+            color = Some(new_color);
 
-        debug!("Calculated temperature: {new_temp} K");
+            match &mut wayland {
+                Some(wayland) => {
+                    wayland.set_temperature(new_color);
+                    info!("Updated temperature to {new_color}");
+                }
+                None => {
+                    let time = now.to_zoned(TimeZone::system());
+                    println!("{}  {new_color}", time.strftime("%Y-%m-%d %H:%M"));
+                }
+            }
+        }
 
-        if new_temp != temp {
-            temp = new_temp;
-            wayland.set_temperature(temp)?;
-            info!("Updated temperature to {} K", temp);
-        } else {
-            debug!("Temperature unchanged at {} K", temp);
+        let interval = sleep(scheduler.clock().real_duration(UPDATE_INTERVAL));
+        match &mut wayland {
+            Some(wayland) => select! {
+                errors = wayland.poll() => {
+                    for err in errors {
+                        warn!("{err}");
+                    }
+                }
+                _ = interval => (),
+            },
+            None => interval.await,
         }
     }
 }
-
-/// Format time as HH:MM
-fn time_of(timestamp: Timestamp) -> impl Display {
-    let timezone = TimeZone::system();
-    let zoned = timestamp.to_zoned(timezone);
-
-    zoned.time().strftime("%H:%M")
-}
@@ -0,0 +1,298 @@
+use crate::{
+    config::Place,
+    location::{Fix, LocationEvent, LocationProvider, ProviderState, Source, connect_then},
+};
+use async_stream::stream;
+use futures::{Stream, StreamExt, stream::LocalBoxStream};
+use jiff::Timestamp;
+use log::{debug, info, warn};
+use tokio::select;
+use zbus::{Connection, proxy};
+use zvariant::OwnedObjectPath;
+
+/// A connection NetworkManager has activated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveNetwork {
+    /// Name of the connection profile
+    pub id: String,
+    /// Network name, for Wi-Fi connections
+    pub ssid: Option<String>,
+}
+
+/// Location provider picking a configured place by the active network
+#[derive(Debug, Clone)]
+pub struct PlacesProvider {
+    places: Vec<Place>,
+}
+
+impl PlacesProvider {
+    pub fn new(places: Vec<Place>) -> Self {
+        Self { places }
+    }
+}
+
+impl LocationProvider for PlacesProvider {
+    fn source(&self) -> Source {
+        Source::Places
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        connect_then(Source::Places, location_stream(self.places.clone()))
+    }
+}
+
+/// Coordinates of the configured place matching the active NetworkManager connections,
+/// updated whenever they change
+pub async fn location_stream(
+    places: Vec<Place>,
+) -> color_eyre::Result<impl Stream<Item = LocationEvent>> {
+    let connection = Connection::system().await?;
+    let network_manager = NetworkManagerProxy::new(&connection).await?;
+    let mut connections_changed = network_manager.receive_active_connections_changed().await;
+    let mut owner_changed = network_manager.inner().receive_owner_changed().await?;
+
+    let location_stream = stream! {
+        let mut tracker = PlaceTracker::default();
+
+        yield LocationEvent::State(ProviderState::Connecting);
+
+        loop {
+            match active_networks(&connection, &network_manager).await {
+                Ok(networks) => {
+                    debug!("Active connections: {networks:?}");
+
+                    for event in tracker.update(&places, &networks) {
+                        yield event;
+                    }
+                }
+                Err(err) => {
+                    warn!("Failed to read active connections from NetworkManager: {err}");
+                    yield tracker.failed();
+                }
+            }
+
+            // A restarted NetworkManager doesn't necessarily signal the connections it resumes
+            select! {
+                Some(_) = connections_changed.next() => (),
+                Some(_) = owner_changed.next() => (),
+                else => break,
+            }
+        }
+
+        yield LocationEvent::State(ProviderState::Disconnected);
+    };
+
+    Ok(location_stream)
+}
+
+/// The place last reported, so only changes of the place are reported
+#[derive(Debug, Default)]
+struct PlaceTracker {
+    /// Name of the place, `Some(None)` when none matches, `None` until reported since reading the
+    /// connections last succeeded
+    reported: Option<Option<String>>,
+}
+
+impl PlaceTracker {
+    /// Events for the currently active `networks`, none if the place is unchanged
+    fn update(&mut self, places: &[Place], networks: &[ActiveNetwork]) -> Vec<LocationEvent> {
+        let place = places.iter().find(|place| place.matches(networks));
+        let name = place.map(|place| place.name.clone());
+        if self.reported.as_ref() == Some(&name) {
+            return Vec::new();
+        }
+        self.reported = Some(name);
+
+        match place {
+            Some(place) => {
+                info!("At place {:?}", place.name);
+                vec![
+                    LocationEvent::State(ProviderState::Running),
+                    LocationEvent::Fix(Fix {
+                        coordinates: place.coordinates(),
+                        source: Source::Places,
+                        accuracy: None,
+                        timestamp: Timestamp::now(),
+                    }),
+                ]
+            }
+            None => {
+                // Lets the next location provider take over
+                info!("No place matches the active connections");
+                vec![LocationEvent::State(ProviderState::Disconnected)]
+            }
+        }
+    }
+
+    /// Reading the connections failed, the place is reported again once it succeeds
+    fn failed(&mut self) -> LocationEvent {
+        self.reported = None;
+
+        LocationEvent::State(ProviderState::Disconnected)
+    }
+}
+
+async fn active_networks(
+    connection: &Connection,
+    network_manager: &NetworkManagerProxy<'_>,
+) -> zbus::Result<Vec<ActiveNetwork>> {
+    let mut networks = Vec::new();
+
+    for path in network_manager.active_connections().await? {
+        let active_connection = ActiveConnectionProxy::builder(connection)
+            .path(path)?
+            .build()
+            .await?;
+
+        let id = active_connection.id().await?;
+        let ssid = access_point_ssid(connection, active_connection.specific_object().await?).await;
+
+        networks.push(ActiveNetwork { id, ssid });
+    }
+
+    Ok(networks)
+}
+
+/// SSID of the access point a Wi-Fi connection uses, `None` for other connections
+async fn access_point_ssid(connection: &Connection, path: OwnedObjectPath) -> Option<String> {
+    if path.as_str() == "/" {
+        return None;
+    }
+
+    let access_point = AccessPointProxy::builder(connection)
+        .path(path)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let ssid = access_point.ssid().await.ok()?;
+
+    Some(String::from_utf8_lossy(&ssid).into_owned())
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    /// ActiveConnections property
+    #[zbus(property)]
+    fn active_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.Connection.Active"
+)]
+trait ActiveConnection {
+    /// Id property
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    /// SpecificObject property, the access point for Wi-Fi connections
+    #[zbus(property)]
+    fn specific_object(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    default_service = "org.freedesktop.NetworkManager",
+    interface = "org.freedesktop.NetworkManager.AccessPoint"
+)]
+trait AccessPoint {
+    /// Ssid property
+    #[zbus(property)]
+    fn ssid(&self) -> zbus::Result<Vec<u8>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(name: &str, latitude: f64, connections: &[&str], ssids: &[&str]) -> Place {
+        toml::from_str(&format!(
+            "name = {name:?}\nlatitude = {latitude:?}\nlongitude = 13.4\n\
+             connections = {connections:?}\nssids = {ssids:?}"
+        ))
+        .unwrap()
+    }
+
+    fn wifi(id: &str, ssid: &str) -> ActiveNetwork {
+        ActiveNetwork {
+            id: id.to_owned(),
+            ssid: Some(ssid.to_owned()),
+        }
+    }
+
+    fn wired(id: &str) -> ActiveNetwork {
+        ActiveNetwork {
+            id: id.to_owned(),
+            ssid: None,
+        }
+    }
+
+    /// Latitude of the reported fix, `None` for a disconnect, or `Err` for anything else
+    fn reported(events: &[LocationEvent]) -> Result<Option<f64>, String> {
+        match events {
+            [
+                LocationEvent::State(ProviderState::Running),
+                LocationEvent::Fix(fix),
+            ] => Ok(Some(fix.coordinates.latitude)),
+            [LocationEvent::State(ProviderState::Disconnected)] => Ok(None),
+            events => Err(format!("{events:?}")),
+        }
+    }
+
+    #[test]
+    fn matches_by_ssid_or_connection_id() {
+        let home = place("home", 52.5, &["Home Ethernet"], &["home-wifi", "home-5g"]);
+
+        assert!(home.matches(&[wifi("Home WiFi", "home-5g")]));
+        assert!(home.matches(&[wired("Home Ethernet")]));
+        assert!(home.matches(&[wired("VPN"), wifi("Other", "home-wifi")]));
+        assert!(!home.matches(&[wifi("Home Ethernet 2", "cafe")]));
+        assert!(!home.matches(&[wired("home-wifi")]));
+        assert!(!home.matches(&[]));
+    }
+
+    #[test]
+    fn reports_only_changes_of_the_place() {
+        let places = [
+            place("home", 52.5, &[], &["home-wifi"]),
+            place("office", 48.1, &["Office LAN"], &[]),
+        ];
+        let mut tracker = PlaceTracker::default();
+
+        let home = [wifi("Home", "home-wifi")];
+        assert_eq!(reported(&tracker.update(&places, &home)), Ok(Some(52.5)));
+        assert!(tracker.update(&places, &home).is_empty());
+
+        let office = [wired("Office LAN"), wifi("Guest", "office-guest")];
+        assert_eq!(reported(&tracker.update(&places, &office)), Ok(Some(48.1)));
+
+        // Disconnected while no place matches, once
+        let elsewhere = [wifi("Cafe", "cafe")];
+        assert_eq!(reported(&tracker.update(&places, &elsewhere)), Ok(None));
+        assert!(tracker.update(&places, &elsewhere).is_empty());
+        assert!(tracker.update(&places, &[]).is_empty());
+
+        assert_eq!(reported(&tracker.update(&places, &home)), Ok(Some(52.5)));
+    }
+
+    #[test]
+    fn reports_again_after_a_failure() {
+        let places = [place("home", 52.5, &[], &["home-wifi"])];
+        let mut tracker = PlaceTracker::default();
+
+        // Nothing matching at first is reported too
+        assert_eq!(reported(&tracker.update(&places, &[])), Ok(None));
+
+        let home = [wifi("Home", "home-wifi")];
+        assert_eq!(reported(&tracker.update(&places, &home)), Ok(Some(52.5)));
+        assert_eq!(reported(&[tracker.failed()]), Ok(None));
+        assert_eq!(reported(&tracker.update(&places, &home)), Ok(Some(52.5)));
+
+        assert_eq!(reported(&[tracker.failed()]), Ok(None));
+        assert_eq!(reported(&tracker.update(&places, &[])), Ok(None));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::color::{ColorSetting, fill_colorramp};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs::File,
+    io::{self, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// A gamma ramp in a sealed memfd, which can be handed to the compositor any number of times
+#[derive(Debug)]
+pub struct Ramp {
+    file: File,
+}
+
+impl Ramp {
+    /// Red, green and blue ramps of `size` entries each for `color`
+    fn new(size: usize, color: ColorSetting) -> color_eyre::Result<Self> {
+        let mut table = vec![0u16; size * 3];
+        let (r, rest) = table.split_at_mut(size);
+        let (g, b) = rest.split_at_mut(size);
+        fill_colorramp(r, g, b, size, color)?;
+
+        let fd = unsafe {
+            libc::memfd_create(
+                c"ramp-buffer".as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+        if fd == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(bytemuck::cast_slice(&table))?;
+
+        // The compositor can rely on the contents never changing
+        let seals =
+            libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self { file })
+    }
+
+    /// A new file description of the ramp, so every reader starts at offset 0
+    pub fn open(&self) -> io::Result<OwnedFd> {
+        let file = File::open(format!("/proc/self/fd/{}", self.file.as_raw_fd()))?;
+
+        Ok(file.into())
+    }
+}
+
+/// Ramps of the current color, shared by all outputs with the same ramp size and brightness
+#[derive(Debug, Default)]
+pub struct RampCache {
+    /// By size, temperature and the bits of the brightness
+    ramps: HashMap<(usize, u16, u64), Ramp>,
+}
+
+impl RampCache {
+    /// Ramp of `size` entries for `color`, computed once until the cache is cleared
+    pub fn get(&mut self, size: usize, color: ColorSetting) -> color_eyre::Result<&Ramp> {
+        let key = (size, color.temperature, color.brightness.to_bits());
+
+        match self.ramps.entry(key) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(Ramp::new(size, color)?)),
+        }
+    }
+
+    /// Drop the ramps of the previous color
+    pub fn clear(&mut self) {
+        self.ramps.clear();
+    }
+}
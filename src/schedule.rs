@@ -0,0 +1,697 @@
+use crate::{
+    color::ColorSetting,
+    config::{AltitudeCurve, Anchor, Config, Keyframe, WarmthLimit},
+    location::Coordinates,
+    solar::{self, CIVIL_TWILIGHT_ANGLE, Direction, SUNRISE_ANGLE},
+    transition::Transition,
+};
+use jiff::{SignedDuration, Timestamp, civil::Date, tz::TimeZone};
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sun {
+    pub dawn: Timestamp,
+    pub sunrise: Timestamp,
+    pub solar_noon: Timestamp,
+    pub sunset: Timestamp,
+    pub dusk: Timestamp,
+}
+
+impl Sun {
+    pub fn time_of(&self, event: SunEvent) -> Timestamp {
+        match event {
+            SunEvent::Dawn => self.dawn,
+            SunEvent::Sunrise => self.sunrise,
+            SunEvent::SolarNoon => self.solar_noon,
+            SunEvent::Sunset => self.sunset,
+            SunEvent::Dusk => self.dusk,
+        }
+    }
+}
+
+/// Solar events of a day, in the order they happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SunEvent {
+    Dawn,
+    Sunrise,
+    SolarNoon,
+    Sunset,
+    Dusk,
+}
+
+impl SunEvent {
+    pub const ALL: [SunEvent; 5] = [
+        SunEvent::Dawn,
+        SunEvent::Sunrise,
+        SunEvent::SolarNoon,
+        SunEvent::Sunset,
+        SunEvent::Dusk,
+    ];
+}
+
+impl Display for SunEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SunEvent::Dawn => write!(f, "dawn"),
+            SunEvent::Sunrise => write!(f, "sunrise"),
+            SunEvent::SolarNoon => write!(f, "solar-noon"),
+            SunEvent::Sunset => write!(f, "sunset"),
+            SunEvent::Dusk => write!(f, "dusk"),
+        }
+    }
+}
+
+/// A sun event resolved to a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub event: SunEvent,
+    pub time: Timestamp,
+}
+
+/// Latest sun event at or before `now`
+pub fn previous_event(now: Timestamp, coordinates: Coordinates) -> Event {
+    let events = events_around(now, coordinates);
+    let next_index = events.partition_point(|event| event.time <= now);
+
+    events[next_index.saturating_sub(1)]
+}
+
+/// Earliest sun event after `now`
+pub fn next_event(now: Timestamp, coordinates: Coordinates) -> Event {
+    let events = events_around(now, coordinates);
+    let next_index = events.partition_point(|event| event.time <= now);
+
+    events[next_index.min(events.len() - 1)]
+}
+
+/// Sun events of the solar days before, closest to and after `now`, sorted by time
+///
+/// Events of a solar day are at most half a day from its solar noon, so these always include
+/// events before and after `now`, wherever the local day boundary falls.
+fn events_around(now: Timestamp, coordinates: Coordinates) -> Vec<Event> {
+    let mut events: Vec<Event> = [-1, 0, 1]
+        .into_iter()
+        .map(|day| calculate_sun(now + SignedDuration::from_hours(24 * day), coordinates))
+        .flat_map(|sun| {
+            SunEvent::ALL.map(|event| Event {
+                event,
+                time: sun.time_of(event),
+            })
+        })
+        .collect();
+    events.sort_by_key(|event| event.time);
+
+    events
+}
+
+/// Sun events of the solar day closest to `now`
+pub fn calculate_sun(now: Timestamp, coordinates: Coordinates) -> Sun {
+    let event = |angle, direction| solar::time_at_angle(now, angle, direction, coordinates);
+
+    Sun {
+        dawn: event(CIVIL_TWILIGHT_ANGLE, Direction::Rising),
+        sunrise: event(SUNRISE_ANGLE, Direction::Rising),
+        solar_noon: solar::solar_noon(now, coordinates.longitude),
+        sunset: event(SUNRISE_ANGLE, Direction::Setting),
+        dusk: event(CIVIL_TWILIGHT_ANGLE, Direction::Setting),
+    }
+}
+
+/// Altitude of the sun above the horizon at `now`, in degrees
+pub fn sun_altitude(now: Timestamp, coordinates: Coordinates) -> f64 {
+    solar::position(now, coordinates).altitude
+}
+
+/// Color at a sun altitude, interpolated between the surrounding points of the curve
+///
+/// Below the lowest and above the highest point the color is held constant.
+fn altitude_color(
+    altitude: f64,
+    curve: &AltitudeCurve,
+    transition: &Transition,
+) -> Option<ColorSetting> {
+    let points = &curve.points;
+    let next_index = points.partition_point(|point| point.altitude <= altitude);
+
+    match (next_index.checked_sub(1), points.get(next_index)) {
+        (Some(previous), Some(next)) => {
+            let previous = &points[previous];
+            let position = (altitude - previous.altitude) / (next.altitude - previous.altitude);
+            Some(transition.interpolate(position, previous.color(), next.color()))
+        }
+        (Some(previous), None) => Some(points[previous].color()),
+        (None, Some(next)) => Some(next.color()),
+        (None, None) => None,
+    }
+}
+
+/// A keyframe resolved to a point in time, settings between keyframes are interpolated
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ResolvedKeyframe {
+    time: Timestamp,
+    color: ColorSetting,
+}
+
+/// Color setting at `now`, interpolated between the surrounding keyframes
+///
+/// Before the first and after the last keyframe the setting is held constant.
+fn interpolate_keyframes(
+    now: Timestamp,
+    keyframes: &[ResolvedKeyframe],
+    transition: &Transition,
+) -> Option<ColorSetting> {
+    let next_index = keyframes.partition_point(|keyframe| keyframe.time <= now);
+
+    let (previous, next) = match (next_index.checked_sub(1), keyframes.get(next_index)) {
+        (Some(previous), Some(next)) => (keyframes[previous], *next),
+        (Some(previous), None) => return Some(keyframes[previous].color),
+        (None, Some(next)) => return Some(next.color),
+        (None, None) => return None,
+    };
+
+    if previous.time == next.time {
+        return Some(next.color);
+    }
+    let time_pos = (now - previous.time).get_seconds() as f64
+        / (next.time - previous.time).get_seconds() as f64;
+    Some(transition.interpolate(time_pos, previous.color, next.color))
+}
+
+/// Sun events of the solar day around noon of a local date
+fn sun_on(date: Date, coordinates: Coordinates, timezone: &TimeZone) -> Result<Sun, jiff::Error> {
+    let noon = date.at(12, 0, 0, 0).to_zoned(timezone.clone())?;
+
+    Ok(calculate_sun(noon.timestamp(), coordinates))
+}
+
+/// Configuration in effect on a local date, with that date's override and season applied
+fn config_on(
+    config: &Config,
+    date: Date,
+    coordinates: Coordinates,
+    timezone: &TimeZone,
+) -> Result<Config, jiff::Error> {
+    let config = match config.active_override(date) {
+        Some(day_override) => config.with_override(day_override),
+        None => config.clone(),
+    };
+    let sun = sun_on(date, coordinates, timezone)?;
+
+    Ok(config.with_season(date.month(), sun.sunset.duration_since(sun.sunrise)))
+}
+
+/// Resolve keyframes around `now`, sorted by time
+///
+/// Sun anchors are resolved for the solar days around `now` and clock times for the local days
+/// around `now`, so both always bracket `now` even where the local day boundary is far from
+/// solar midnight. The keyframes of each day come from `day_config` of its local date, for sun
+/// anchors the date of the solar noon.
+fn resolve_keyframes(
+    now: Timestamp,
+    day_config: &impl Fn(Date) -> Result<Config, jiff::Error>,
+    keyframes: impl Fn(&Config) -> Vec<Keyframe>,
+    coordinates: Coordinates,
+    timezone: &TimeZone,
+) -> Result<Vec<ResolvedKeyframe>, jiff::Error> {
+    let today = now.to_zoned(timezone.clone()).date();
+    let dates = [today.yesterday()?, today, today.tomorrow()?];
+    let suns = [-1, 0, 1]
+        .map(|day| calculate_sun(now + SignedDuration::from_hours(24 * day), coordinates));
+
+    let mut resolved = Vec::new();
+    for day in 0..3 {
+        let solar_date = suns[day].solar_noon.to_zoned(timezone.clone()).date();
+        let sun_keyframes = keyframes(&day_config(solar_date)?)
+            .into_iter()
+            .filter(|keyframe| matches!(keyframe.anchor, Anchor::Sun(_)));
+        let clock_keyframes = keyframes(&day_config(dates[day])?)
+            .into_iter()
+            .filter(|keyframe| matches!(keyframe.anchor, Anchor::Time(_)));
+
+        for keyframe in sun_keyframes.chain(clock_keyframes) {
+            let anchor_time = match keyframe.anchor {
+                Anchor::Sun(event) => suns[day].time_of(event),
+                Anchor::Time(time) => dates[day]
+                    .to_datetime(time)
+                    .to_zoned(timezone.clone())?
+                    .timestamp(),
+            };
+
+            resolved.push(ResolvedKeyframe {
+                time: anchor_time.checked_add(keyframe.offset)?,
+                color: keyframe.color(),
+            });
+        }
+    }
+    resolved.sort_by_key(|keyframe| keyframe.time);
+
+    Ok(resolved)
+}
+
+pub fn get_temperature(
+    now: Timestamp,
+    coordinates: Coordinates,
+    config: &Config,
+    timezone: &TimeZone,
+) -> Result<ColorSetting, jiff::Error> {
+    let today = now.to_zoned(timezone.clone()).date();
+    let day_config = |date| config_on(config, date, coordinates, timezone);
+
+    let color = scheduled_color(now, coordinates, &day_config, timezone)?;
+
+    let config = day_config(today)?;
+    let warmth_limit = config
+        .season
+        .as_ref()
+        .and_then(|season| season.warmth_limit.as_ref());
+    match warmth_limit {
+        Some(limit) => {
+            let sun = sun_on(today, coordinates, timezone)?;
+            apply_warmth_limit(now, color, limit, sun, &config, timezone)
+        }
+        None => Ok(color),
+    }
+}
+
+/// Color of the base schedule combined with bedtime
+///
+/// `day_config` gives the configuration in effect on a local date.
+fn scheduled_color(
+    now: Timestamp,
+    coordinates: Coordinates,
+    day_config: &impl Fn(Date) -> Result<Config, jiff::Error>,
+    timezone: &TimeZone,
+) -> Result<ColorSetting, jiff::Error> {
+    let config = &day_config(now.to_zoned(timezone.clone()).date())?;
+    let color = match &config.altitude_curve {
+        Some(curve) => altitude_color(sun_altitude(now, coordinates), curve, &config.transition),
+        None => {
+            let keyframes = resolve_keyframes(
+                now,
+                day_config,
+                Config::schedule_keyframes,
+                coordinates,
+                timezone,
+            )?;
+            interpolate_keyframes(now, &keyframes, &config.transition)
+        }
+    };
+    let color = color.unwrap_or(ColorSetting::new(config.day_temperature));
+
+    let Some(bedtime) = &config.bedtime else {
+        return Ok(color);
+    };
+
+    let bedtime_keyframes = |config: &Config| {
+        config
+            .bedtime
+            .as_ref()
+            .map_or_else(Vec::new, |bedtime| bedtime.keyframes(config))
+    };
+    let keyframes = resolve_keyframes(now, day_config, bedtime_keyframes, coordinates, timezone)?;
+    let Some(bedtime_color) = interpolate_keyframes(now, &keyframes, &config.transition) else {
+        return Ok(color);
+    };
+
+    if bedtime.replace_solar {
+        Ok(bedtime_color)
+    } else {
+        // The warmer and dimmer of the two schedules wins
+        Ok(ColorSetting {
+            temperature: color.temperature.min(bedtime_color.temperature),
+            brightness: color.brightness.min(bedtime_color.brightness),
+        })
+    }
+}
+
+/// Keep the temperature at or above the limit from sunrise until its clock time, and blend back
+/// to the scheduled color over the limit's ramp afterwards
+fn apply_warmth_limit(
+    now: Timestamp,
+    color: ColorSetting,
+    limit: &WarmthLimit,
+    sun: Sun,
+    config: &Config,
+    timezone: &TimeZone,
+) -> Result<ColorSetting, jiff::Error> {
+    if color.temperature >= limit.temperature || now < sun.sunrise {
+        return Ok(color);
+    }
+    let limited = ColorSetting {
+        temperature: limit.temperature,
+        ..color
+    };
+
+    let today = now.to_zoned(timezone.clone()).date();
+    let until = today.to_datetime(limit.until).to_zoned(timezone.clone())?;
+    let until = until.timestamp();
+
+    if now < until {
+        return Ok(limited);
+    }
+
+    let ramp = limit.ramp_duration.as_secs_f64();
+    if ramp <= 0.0 {
+        return Ok(color);
+    }
+    let time_pos = now.duration_since(until).as_secs_f64() / ramp;
+    Ok(config.transition.interpolate(time_pos, limited, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+            altitude: 0.0,
+        }
+    }
+
+    /// Timestamps every 7 minutes over the three local days around `date`
+    fn steps_around(date: Date, timezone: &TimeZone) -> impl Iterator<Item = Timestamp> {
+        let start = date
+            .yesterday()
+            .unwrap()
+            .to_zoned(timezone.clone())
+            .unwrap()
+            .timestamp();
+
+        (0..3 * 24 * 60 / 7).map(move |step| start + SignedDuration::from_mins(7 * step))
+    }
+
+    /// Date of the first transition of `timezone` in `year`, usually the start of DST
+    fn first_transition(timezone: &TimeZone, year: i16) -> Date {
+        let start = jiff::civil::date(year, 1, 1)
+            .to_zoned(timezone.clone())
+            .unwrap()
+            .timestamp();
+        let transition = timezone.following(start).next().unwrap();
+
+        transition.timestamp().to_zoned(timezone.clone()).date()
+    }
+
+    fn assert_brackets(timezone: &str, coordinates: Coordinates, dates: &[Date]) {
+        let timezone = TimeZone::get(timezone).unwrap();
+
+        for &date in dates {
+            for now in steps_around(date, &timezone) {
+                let previous = previous_event(now, coordinates);
+                let next = next_event(now, coordinates);
+
+                assert!(previous.time <= now, "{previous:?} after {now}");
+                assert!(now < next.time, "{next:?} not after {now}");
+                assert!(
+                    next.time.duration_since(previous.time) < SignedDuration::from_hours(24),
+                    "{previous:?} and {next:?} around {now} are too far apart"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn events_bracket_now_across_dst() {
+        let berlin = TimeZone::get("Europe/Berlin").unwrap();
+        let dates = [
+            first_transition(&berlin, 2026),
+            jiff::civil::date(2026, 10, 25),
+        ];
+        assert_brackets("Europe/Berlin", coordinates(52.52, 13.40), &dates);
+
+        let new_york = TimeZone::get("America/New_York").unwrap();
+        let dates = [
+            first_transition(&new_york, 2026),
+            jiff::civil::date(2026, 11, 1),
+        ];
+        assert_brackets("America/New_York", coordinates(40.71, -74.01), &dates);
+    }
+
+    #[test]
+    fn events_bracket_now_far_from_the_zone_meridian() {
+        let dates = [
+            jiff::civil::date(2026, 3, 20),
+            jiff::civil::date(2026, 12, 21),
+        ];
+        // Kolkata is 23 minutes ahead of the zone's solar time, Mumbai 39 minutes behind
+        assert_brackets("Asia/Kolkata", coordinates(22.57, 88.36), &dates);
+        assert_brackets("Asia/Kolkata", coordinates(19.08, 72.88), &dates);
+
+        // Chatham's offset is on the other side of the date line from its longitude, and runs half
+        // an hour ahead of solar time, an hour and a half in summer
+        let chatham = TimeZone::get("Pacific/Chatham").unwrap();
+        let dates = [
+            first_transition(&chatham, 2026),
+            jiff::civil::date(2026, 9, 27),
+        ];
+        assert_brackets("Pacific/Chatham", coordinates(-43.95, -176.56), &dates);
+    }
+
+    #[test]
+    fn events_bracket_now_in_polar_night() {
+        let dates = [
+            jiff::civil::date(2026, 12, 21),
+            jiff::civil::date(2026, 6, 21),
+        ];
+        assert_brackets("Europe/Oslo", coordinates(69.65, 18.96), &dates);
+    }
+
+    fn assert_keyframes_bracket(timezone: &str, coordinates: Coordinates, dates: &[Date]) {
+        let timezone = TimeZone::get(timezone).unwrap();
+        let config: Config = toml::from_str(
+            r#"
+            [[keyframes]]
+            anchor = "sunrise"
+            temperature = 6500
+
+            [[keyframes]]
+            anchor = "sunset"
+            temperature = 4500
+
+            # Doesn't exist on the day DST starts in Europe
+            [[keyframes]]
+            anchor = "02:30"
+            temperature = 3400
+
+            [[keyframes]]
+            anchor = "dawn"
+            offset = "-30m"
+            temperature = 3400
+            "#,
+        )
+        .unwrap();
+
+        for &date in dates {
+            for now in steps_around(date, &timezone) {
+                let keyframes = resolve_keyframes(
+                    now,
+                    &|_| Ok(config.clone()),
+                    |config| config.keyframes.clone(),
+                    coordinates,
+                    &timezone,
+                )
+                .unwrap();
+                let next_index = keyframes.partition_point(|keyframe| keyframe.time <= now);
+
+                assert!(
+                    (1..keyframes.len()).contains(&next_index),
+                    "keyframes don't bracket {now}: {keyframes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keyframes_bracket_now_across_dst() {
+        let berlin = TimeZone::get("Europe/Berlin").unwrap();
+        let dates = [
+            first_transition(&berlin, 2026),
+            jiff::civil::date(2026, 10, 25),
+        ];
+        assert_keyframes_bracket("Europe/Berlin", coordinates(52.52, 13.40), &dates);
+
+        let chatham = TimeZone::get("Pacific/Chatham").unwrap();
+        let dates = [
+            first_transition(&chatham, 2026),
+            jiff::civil::date(2026, 9, 27),
+        ];
+        assert_keyframes_bracket("Pacific/Chatham", coordinates(-43.95, -176.56), &dates);
+
+        let dates = [jiff::civil::date(2026, 6, 21)];
+        assert_keyframes_bracket("Asia/Kolkata", coordinates(19.08, 72.88), &dates);
+    }
+
+    #[test]
+    fn temperature_follows_the_sun_across_dst() {
+        let config = Config::default();
+        let day = ColorSetting::new(config.day_temperature);
+        let night = ColorSetting::new(config.night_temperature);
+
+        let cases = [
+            ("Europe/Berlin", coordinates(52.52, 13.40), None),
+            ("Pacific/Chatham", coordinates(-43.95, -176.56), None),
+            // Kolkata doesn't observe DST
+            (
+                "Asia/Kolkata",
+                coordinates(19.08, 72.88),
+                Some(jiff::civil::date(2026, 6, 21)),
+            ),
+        ];
+
+        for (timezone, coordinates, date) in cases {
+            let timezone = TimeZone::get(timezone).unwrap();
+            let date = date.unwrap_or_else(|| first_transition(&timezone, 2026));
+
+            for day_offset in [-1, 0, 1] {
+                let now = date.to_zoned(timezone.clone()).unwrap().timestamp()
+                    + SignedDuration::from_hours(24 * day_offset);
+                let sun = calculate_sun(now, coordinates);
+
+                let at = |time| get_temperature(time, coordinates, &config, &timezone).unwrap();
+                assert_eq!(at(sun.solar_noon), day, "solar noon near {now}");
+                assert_eq!(
+                    at(sun.solar_noon + SignedDuration::from_hours(12)),
+                    night,
+                    "solar midnight near {now}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn overrides_apply_to_their_own_days() {
+        let berlin = TimeZone::get("Europe/Berlin").unwrap();
+        let coordinates = coordinates(52.52, 13.40);
+        let config: Config = toml::from_str(
+            r#"
+            [[overrides]]
+            name = "weekend"
+            weekdays = ["saturday"]
+            night_temperature = 3000
+            "#,
+        )
+        .unwrap();
+        let at = |time| get_temperature(time, coordinates, &config, &berlin).unwrap();
+
+        // Friday evening keeps the regular night, Saturday morning has the override's
+        let friday = jiff::civil::date(2026, 10, 16);
+        let friday_sun = calculate_sun(
+            friday
+                .at(12, 0, 0, 0)
+                .to_zoned(berlin.clone())
+                .unwrap()
+                .timestamp(),
+            coordinates,
+        );
+        let saturday_sun = calculate_sun(
+            friday_sun.solar_noon + SignedDuration::from_hours(24),
+            coordinates,
+        );
+        assert_eq!(at(friday_sun.dusk).temperature, config.night_temperature);
+        assert_eq!(at(saturday_sun.dawn).temperature, 3000);
+
+        // The night blends between the two instead of jumping at midnight
+        let midnight = jiff::civil::date(2026, 10, 17)
+            .to_zoned(berlin.clone())
+            .unwrap()
+            .timestamp();
+        let mut previous = at(midnight - SignedDuration::from_mins(10)).temperature;
+        for minute in -9..=10 {
+            let temperature = at(midnight + SignedDuration::from_mins(minute)).temperature;
+            assert!(
+                previous.abs_diff(temperature) <= 5,
+                "{previous} K to {temperature} K at {minute} min from midnight"
+            );
+            previous = temperature;
+        }
+        let before = at(midnight - SignedDuration::from_mins(10)).temperature;
+        assert!(
+            (3000..config.night_temperature).contains(&before),
+            "{before}"
+        );
+    }
+
+    #[test]
+    fn seasons_apply_to_their_own_days() {
+        let berlin = TimeZone::get("Europe/Berlin").unwrap();
+        let coordinates = coordinates(52.52, 13.40);
+        let config: Config = toml::from_str(
+            r#"
+            [season]
+            night_temperature_by_month = [3100, 3200, 3300, 3400, 3500, 3600, 3700, 3800, 3900, 4000, 3000, 4200]
+            "#,
+        )
+        .unwrap();
+        let at = |time| get_temperature(time, coordinates, &config, &berlin).unwrap();
+
+        // October's night at its last dusk, November's at its first dawn
+        let october = sun_on(jiff::civil::date(2026, 10, 31), coordinates, &berlin).unwrap();
+        let november = sun_on(jiff::civil::date(2026, 11, 1), coordinates, &berlin).unwrap();
+        assert_eq!(at(october.dusk).temperature, 4000);
+        assert_eq!(at(november.dawn).temperature, 3000);
+
+        let midnight = jiff::civil::date(2026, 11, 1)
+            .to_zoned(berlin.clone())
+            .unwrap()
+            .timestamp();
+        let before = at(midnight - SignedDuration::from_mins(1)).temperature;
+        let after = at(midnight + SignedDuration::from_mins(1)).temperature;
+        assert!(before.abs_diff(after) <= 5, "{before} K to {after} K");
+    }
+
+    #[test]
+    fn warmth_limit_holds_until_its_time_and_ramps_off() {
+        let utc = TimeZone::UTC;
+        let at = |time: &str| -> Timestamp { format!("2026-10-18T{time}Z").parse().unwrap() };
+        let sun = Sun {
+            dawn: at("05:30:00"),
+            sunrise: at("06:00:00"),
+            solar_noon: at("12:00:00"),
+            sunset: at("18:00:00"),
+            dusk: at("18:30:00"),
+        };
+        let limit = WarmthLimit {
+            until: jiff::civil::time(8, 0, 0, 0),
+            temperature: 5000,
+            ramp_duration: SignedDuration::from_hours(1),
+        };
+        let config = Config::default();
+        let color = ColorSetting::new(3000);
+        let limited = |time| {
+            apply_warmth_limit(at(time), color, &limit, sun, &config, &utc)
+                .unwrap()
+                .temperature
+        };
+
+        // Before sunrise the schedule is left alone
+        assert_eq!(limited("05:59:00"), 3000);
+        assert_eq!(limited("06:00:00"), 5000);
+        assert_eq!(limited("07:59:00"), 5000);
+
+        // Relaxed over the ramp, then back to the schedule
+        assert_eq!(limited("08:00:00"), 5000);
+        let halfway = config
+            .transition
+            .interpolate(0.5, ColorSetting::new(5000), color);
+        assert_eq!(limited("08:30:00"), halfway.temperature);
+        assert!((3000..5000).contains(&halfway.temperature));
+        assert_eq!(limited("09:00:00"), 3000);
+        assert_eq!(limited("11:00:00"), 3000);
+
+        // Warmer than the schedule is never forced
+        let cool = ColorSetting::new(6000);
+        let result = apply_warmth_limit(at("07:00:00"), cool, &limit, sun, &config, &utc);
+        assert_eq!(result.unwrap(), cool);
+
+        // Without a ramp the limit ends at its time
+        let sudden = WarmthLimit {
+            ramp_duration: SignedDuration::ZERO,
+            ..limit.clone()
+        };
+        let result = apply_warmth_limit(at("08:00:00"), color, &sudden, sun, &config, &utc);
+        assert_eq!(result.unwrap(), color);
+    }
+}
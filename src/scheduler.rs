@@ -0,0 +1,209 @@
+use crate::{
+    clock::Clock,
+    color::ColorSetting,
+    config::Config,
+    location::Coordinates,
+    schedule::{Event, Sun, calculate_sun, get_temperature, next_event, previous_event},
+};
+use jiff::{Timestamp, tz::TimeZone};
+use log::{debug, info};
+use std::fmt::Display;
+
+/// Computes the color for the time of its clock and logs changes of the sun events and the
+/// active override along the way
+pub struct Scheduler<C> {
+    config: Config,
+    clock: C,
+    timezone: TimeZone,
+    sun: Option<Sun>,
+    upcoming_event: Option<Event>,
+    active_override: Option<String>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(config: Config, clock: C, timezone: TimeZone) -> Self {
+        Self {
+            config,
+            clock,
+            timezone,
+            sun: None,
+            upcoming_event: None,
+            active_override: None,
+        }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Name of the override active at the last update
+    pub fn active_override(&self) -> Option<&str> {
+        self.active_override.as_deref()
+    }
+
+    /// Color at the current time of the clock, returned together with that time
+    pub fn update(
+        &mut self,
+        coordinates: Coordinates,
+    ) -> Result<(Timestamp, ColorSetting), jiff::Error> {
+        let now = self.clock.now();
+        let timezone = &self.timezone;
+        debug!("Current time: {}", time_of(now, timezone));
+
+        let new_sun = calculate_sun(now, coordinates);
+
+        if Some(new_sun) != self.sun {
+            self.sun = Some(new_sun);
+
+            info!(
+                "Dawn: {}, Sunrise: {}, Sunset: {}, Dusk: {}",
+                time_of(new_sun.dawn, timezone),
+                time_of(new_sun.sunrise, timezone),
+                time_of(new_sun.sunset, timezone),
+                time_of(new_sun.dusk, timezone)
+            );
+        }
+
+        let new_next_event = next_event(now, coordinates);
+        if Some(new_next_event) != self.upcoming_event {
+            self.upcoming_event = Some(new_next_event);
+            let last_event = previous_event(now, coordinates);
+            info!(
+                "Last event: {} at {}, next event: {} at {}",
+                last_event.event,
+                time_of(last_event.time, timezone),
+                new_next_event.event,
+                time_of(new_next_event.time, timezone)
+            );
+        }
+
+        let today = now.to_zoned(timezone.clone()).date();
+        let new_override = self
+            .config
+            .active_override(today)
+            .map(|day_override| day_override.name.clone());
+
+        if new_override != self.active_override {
+            match &new_override {
+                Some(name) => info!("Override {name:?} is active"),
+                None => info!("No override is active"),
+            }
+            self.active_override = new_override;
+        }
+
+        let color = get_temperature(now, coordinates, &self.config, timezone)?;
+        debug!("Calculated temperature: {color}");
+
+        Ok((now, color))
+    }
+}
+
+/// Format time as HH:MM
+fn time_of(timestamp: Timestamp, timezone: &TimeZone) -> impl Display {
+    let zoned = timestamp.to_zoned(timezone.clone());
+
+    zoned.time().strftime("%H:%M")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use jiff::SignedDuration;
+
+    const BERLIN: Coordinates = Coordinates {
+        latitude: 52.52,
+        longitude: 13.40,
+        altitude: 0.0,
+    };
+
+    fn scheduler(config: Config, start: &str) -> Scheduler<ManualClock> {
+        let timezone = TimeZone::get("Europe/Berlin").unwrap();
+        let start = jiff::civil::DateTime::strptime("%F %H:%M", start)
+            .unwrap()
+            .to_zoned(timezone.clone())
+            .unwrap()
+            .timestamp();
+
+        Scheduler::new(config, ManualClock::new(start), timezone)
+    }
+
+    #[test]
+    fn follows_the_clock_across_keyframes() {
+        let config = Config::default();
+        let mut scheduler = scheduler(config.clone(), "2026-10-16 12:00");
+
+        let (now, color) = scheduler.update(BERLIN).unwrap();
+        assert_eq!(now, scheduler.clock().now());
+        assert_eq!(color.temperature, config.day_temperature);
+
+        // Into the evening transition and past dusk
+        scheduler
+            .clock()
+            .advance(SignedDuration::from_mins(6 * 60 + 30));
+        let (_, color) = scheduler.update(BERLIN).unwrap();
+        assert!(
+            (config.night_temperature + 1..config.day_temperature).contains(&color.temperature),
+            "{color}"
+        );
+        scheduler.clock().advance(SignedDuration::from_hours(2));
+        let (now, color) = scheduler.update(BERLIN).unwrap();
+        assert_eq!(now, scheduler.clock().now());
+        assert_eq!(color.temperature, config.night_temperature);
+    }
+
+    #[test]
+    fn moves_on_to_the_next_day() {
+        let mut scheduler = scheduler(Config::default(), "2026-10-16 23:00");
+
+        scheduler.update(BERLIN).unwrap();
+        let friday = scheduler.sun.unwrap();
+        let friday_event = scheduler.upcoming_event.unwrap();
+
+        scheduler.clock().advance(SignedDuration::from_hours(3));
+        scheduler.update(BERLIN).unwrap();
+        let saturday = scheduler.sun.unwrap();
+        let hours = saturday
+            .solar_noon
+            .duration_since(friday.solar_noon)
+            .as_secs_f64()
+            / 3600.0;
+        assert!(
+            (23.9..24.1).contains(&hours),
+            "{hours} h between solar noons"
+        );
+
+        // Still night, heading for Saturday's dawn
+        let saturday_event = scheduler.upcoming_event.unwrap();
+        assert_eq!(friday_event, saturday_event);
+        assert_eq!(saturday_event.time, saturday.dawn);
+    }
+
+    #[test]
+    fn switches_overrides_by_date() {
+        let config: Config = toml::from_str(
+            r#"
+            [[overrides]]
+            name = "weekend"
+            weekdays = ["saturday", "sunday"]
+            day_temperature = 5000
+            "#,
+        )
+        .unwrap();
+        let mut scheduler = scheduler(config, "2026-10-16 12:00");
+
+        let (_, color) = scheduler.update(BERLIN).unwrap();
+        assert_eq!(scheduler.active_override(), None);
+        assert_eq!(color.temperature, 6500);
+
+        scheduler.clock().advance(SignedDuration::from_hours(24));
+        let (_, color) = scheduler.update(BERLIN).unwrap();
+        assert_eq!(scheduler.active_override(), Some("weekend"));
+        assert_eq!(color.temperature, 5000);
+
+        scheduler.clock().advance(SignedDuration::from_hours(48));
+        let (_, color) = scheduler.update(BERLIN).unwrap();
+        assert_eq!(scheduler.active_override(), None);
+        assert_eq!(color.temperature, 6500);
+    }
+}
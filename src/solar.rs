@@ -0,0 +1,475 @@
+//! Solar position and event times, following the
+//! [NOAA solar calculator](https://gml.noaa.gov/grad/solcalc/calcdetails.html)
+//!
+//! The NOAA calculator implements the low accuracy algorithms of Jean Meeus' _Astronomical
+//! Algorithms_, which are accurate to about a minute for event times between 1800 and 2100.
+
+use crate::location::Coordinates;
+use jiff::Timestamp;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+const JULIAN_UNIX_EPOCH: f64 = 2440587.5;
+const JULIAN_2000: f64 = 2451545.0;
+
+/// Sun altitude at sunrise and sunset, accounting for refraction and the radius of the sun
+pub const SUNRISE_ANGLE: f64 = -0.833;
+/// Sun altitude at civil dawn and dusk
+pub const CIVIL_TWILIGHT_ANGLE: f64 = -6.0;
+
+/// Position of the sun as seen by an observer, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    /// Angle above the horizon, corrected for atmospheric refraction
+    pub altitude: f64,
+    /// Clockwise from north
+    pub azimuth: f64,
+}
+
+/// Whether an event happens before or after solar noon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rising,
+    Setting,
+}
+
+/// Position of the sun at `time`
+pub fn position(time: Timestamp, coordinates: Coordinates) -> Position {
+    let jd = julian_day(time);
+    let sun = SunParameters::at(jd);
+
+    let latitude = coordinates.latitude.to_radians();
+    let declination = sun.declination.to_radians();
+
+    // True solar time in minutes, and the hour angle in degrees
+    let utc_minutes = (jd + 0.5).fract() * 1440.0;
+    let true_solar_time =
+        (utc_minutes + sun.equation_of_time + 4.0 * coordinates.longitude).rem_euclid(1440.0);
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let cos_zenith =
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    let zenith = cos_zenith.clamp(-1.0, 1.0).acos();
+    let altitude = 90.0 - zenith.to_degrees();
+
+    let azimuth = if zenith.sin().abs() < 1e-9 {
+        // The sun is straight above or below, any azimuth is correct
+        180.0
+    } else {
+        let cos_azimuth =
+            (latitude.sin() * zenith.cos() - declination.sin()) / (latitude.cos() * zenith.sin());
+        let azimuth = cos_azimuth.clamp(-1.0, 1.0).acos().to_degrees();
+        if hour_angle > 0.0 {
+            (azimuth + 180.0).rem_euclid(360.0)
+        } else {
+            (540.0 - azimuth).rem_euclid(360.0)
+        }
+    };
+
+    Position {
+        altitude: altitude + refraction(altitude),
+        azimuth,
+    }
+}
+
+/// Solar noon closest to `time`
+pub fn solar_noon(time: Timestamp, longitude: f64) -> Timestamp {
+    from_julian_day(transit_near(julian_day(time), longitude))
+}
+
+/// Time the sun crosses `angle` degrees of altitude on the solar day closest to `time`
+///
+/// The horizon is lowered for observers above sea level. If the sun doesn't cross the angle
+/// that day, solar noon is returned when it stays below the angle and solar midnight when it stays
+/// above, so a day without sunrise is all night and a day without sunset is all day.
+pub fn time_at_angle(
+    time: Timestamp,
+    angle: f64,
+    direction: Direction,
+    coordinates: Coordinates,
+) -> Timestamp {
+    let angle = angle - horizon_dip(coordinates.altitude);
+    let latitude = coordinates.latitude.to_radians();
+    let mean_noon = mean_noon(julian_day(time), coordinates.longitude);
+
+    let sign = match direction {
+        Direction::Rising => -1.0,
+        Direction::Setting => 1.0,
+    };
+
+    // The declination changes over the day, so refine the estimate at the event itself
+    let mut event = mean_noon;
+    for _ in 0..3 {
+        let sun = SunParameters::at(event);
+        let declination = sun.declination.to_radians();
+
+        let cos_hour_angle = (angle.to_radians().sin() - latitude.sin() * declination.sin())
+            / (latitude.cos() * declination.cos());
+        let hour_angle = cos_hour_angle.clamp(-1.0, 1.0).acos().to_degrees();
+
+        let transit = mean_noon - sun.equation_of_time / 1440.0;
+        event = transit + sign * hour_angle / 360.0;
+    }
+
+    from_julian_day(event)
+}
+
+/// Lowering of the visible horizon in degrees for an observer `altitude` meters above sea level
+fn horizon_dip(altitude: f64) -> f64 {
+    if altitude <= 0.0 {
+        return 0.0;
+    }
+    1.76 * altitude.sqrt() / 60.0
+}
+
+/// Atmospheric refraction correction in degrees, from the NOAA calculator
+fn refraction(altitude: f64) -> f64 {
+    if altitude > 85.0 {
+        return 0.0;
+    }
+
+    let tan = altitude.to_radians().tan();
+    let arcseconds = if altitude > 5.0 {
+        58.1 / tan - 0.07 / tan.powi(3) + 0.000086 / tan.powi(5)
+    } else if altitude > -0.575 {
+        1735.0 + altitude * (-518.2 + altitude * (103.4 + altitude * (-12.79 + altitude * 0.711)))
+    } else {
+        -20.772 / tan
+    };
+
+    arcseconds / 3600.0
+}
+
+/// Julian day of the mean solar noon closest to `jd`
+fn mean_noon(jd: f64, longitude: f64) -> f64 {
+    // Mean noon is at noon UTC shifted by the longitude
+    let day = (jd - JULIAN_2000 + longitude / 360.0).round();
+    JULIAN_2000 + day - longitude / 360.0
+}
+
+/// Julian day of the solar transit closest to `jd`, mean noon corrected by the equation of time
+fn transit_near(jd: f64, longitude: f64) -> f64 {
+    let mean_noon = mean_noon(jd, longitude);
+
+    let mut transit = mean_noon;
+    for _ in 0..2 {
+        transit = mean_noon - SunParameters::at(transit).equation_of_time / 1440.0;
+    }
+    transit
+}
+
+fn julian_day(time: Timestamp) -> f64 {
+    time.as_millisecond() as f64 / 1000.0 / SECONDS_PER_DAY + JULIAN_UNIX_EPOCH
+}
+
+fn from_julian_day(jd: f64) -> Timestamp {
+    let milliseconds = ((jd - JULIAN_UNIX_EPOCH) * SECONDS_PER_DAY * 1000.0).round() as i64;
+    Timestamp::from_millisecond(milliseconds).unwrap_or(Timestamp::UNIX_EPOCH)
+}
+
+/// Declination and equation of time, the parameters that vary over the year
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SunParameters {
+    /// Declination in degrees
+    declination: f64,
+    /// Difference between true and mean solar time, in minutes
+    equation_of_time: f64,
+}
+
+impl SunParameters {
+    fn at(jd: f64) -> Self {
+        // Julian centuries since J2000.0
+        let t = (jd - JULIAN_2000) / 36525.0;
+
+        let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+        let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+        let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+
+        let m = mean_anomaly.to_radians();
+        let equation_of_center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+            + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+            + (3.0 * m).sin() * 0.000289;
+        let true_longitude = mean_longitude + equation_of_center;
+
+        let omega = (125.04 - 1934.136 * t).to_radians();
+        let apparent_longitude = true_longitude - 0.00569 - 0.00478 * omega.sin();
+
+        let mean_obliquity =
+            23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+        let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+        let declination = (obliquity.sin() * apparent_longitude.to_radians().sin())
+            .asin()
+            .to_degrees();
+
+        let y = (obliquity / 2.0).tan().powi(2);
+        let l0 = mean_longitude.to_radians();
+        let equation_of_time = 4.0
+            * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+                + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+                - 0.5 * y * y * (4.0 * l0).sin()
+                - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+            .to_degrees();
+
+        Self {
+            declination,
+            equation_of_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+            altitude: 0.0,
+        }
+    }
+
+    fn timestamp(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn assert_close(actual: Timestamp, expected: &str, tolerance_secs: i64) {
+        let difference = actual.duration_since(timestamp(expected)).as_secs().abs();
+        assert!(
+            difference <= tolerance_secs,
+            "{actual} is {difference} s from {expected}"
+        );
+    }
+
+    /// Example 25.a of Meeus' Astronomical Algorithms, the basis of the NOAA calculator
+    #[test]
+    fn declination_matches_meeus() {
+        let sun = SunParameters::at(julian_day(timestamp("1992-10-13T00:00:00Z")));
+        assert!((sun.declination - -7.78507).abs() < 0.0001, "{sun:?}");
+    }
+
+    /// Example 28.b of Meeus' Astronomical Algorithms, 13m 42.6s
+    #[test]
+    fn equation_of_time_matches_meeus() {
+        let sun = SunParameters::at(julian_day(timestamp("1992-10-13T00:00:00Z")));
+        assert!((sun.equation_of_time - 13.71).abs() < 0.01, "{sun:?}");
+    }
+
+    /// Equinox and solstice instants published by the US Naval Observatory
+    #[test]
+    fn declination_at_equinox_and_solstice() {
+        let equinox = SunParameters::at(julian_day(timestamp("2024-03-20T03:06:00Z")));
+        assert!(equinox.declination.abs() < 0.01, "{equinox:?}");
+
+        let solstice = SunParameters::at(julian_day(timestamp("2024-06-20T20:51:00Z")));
+        assert!((solstice.declination - 23.44).abs() < 0.01, "{solstice:?}");
+    }
+
+    #[test]
+    fn solar_noon_at_greenwich() {
+        // The equation of time peaks at about 16m 30s in early November
+        let noon = solar_noon(timestamp("2026-11-03T12:00:00Z"), 0.0);
+        assert_close(noon, "2026-11-03T11:43:30Z", 30);
+    }
+
+    /// Reference values from the cell formulas of NOAA's solar calculation spreadsheet
+    /// (`NOAA_Solar_Calculations_day.xls`), evaluated at `time`. The spreadsheet takes the
+    /// declination at that time for the whole day, so events may differ by a few seconds.
+    struct NoaaReference {
+        coordinates: Coordinates,
+        time: &'static str,
+        /// Civil dawn, sunrise, solar noon, sunset and civil dusk
+        events: [&'static str; 5],
+        /// Time of the position below
+        position_time: &'static str,
+        altitude: f64,
+        azimuth: f64,
+    }
+
+    const NOAA_REFERENCES: [NoaaReference; 4] = [
+        // Boulder, summer solstice
+        NoaaReference {
+            coordinates: Coordinates {
+                latitude: 40.0,
+                longitude: -105.0,
+                altitude: 0.0,
+            },
+            time: "2026-06-21T19:00:00Z",
+            events: [
+                "2026-06-21T10:58:34Z",
+                "2026-06-21T11:31:27Z",
+                "2026-06-21T19:01:53Z",
+                "2026-06-22T02:32:19Z",
+                "2026-06-22T03:05:12Z",
+            ],
+            position_time: "2026-06-21T16:00:00Z",
+            altitude: 48.48,
+            azimuth: 99.46,
+        },
+        // Sydney, southern summer solstice, the day starts on the previous UTC date
+        NoaaReference {
+            coordinates: Coordinates {
+                latitude: -33.87,
+                longitude: 151.21,
+                altitude: 0.0,
+            },
+            time: "2026-12-21T02:00:00Z",
+            events: [
+                "2026-12-20T18:11:37Z",
+                "2026-12-20T18:40:47Z",
+                "2026-12-21T01:53:01Z",
+                "2026-12-21T09:05:16Z",
+                "2026-12-21T09:34:26Z",
+            ],
+            position_time: "2026-12-21T06:30:00Z",
+            altitude: 29.50,
+            azimuth: 260.16,
+        },
+        // Reykjavik, equinox, with long twilight
+        NoaaReference {
+            coordinates: Coordinates {
+                latitude: 64.15,
+                longitude: -21.94,
+                altitude: 0.0,
+            },
+            time: "2026-03-20T13:00:00Z",
+            events: [
+                "2026-03-20T06:39:55Z",
+                "2026-03-20T07:27:45Z",
+                "2026-03-20T13:35:10Z",
+                "2026-03-20T19:42:36Z",
+                "2026-03-20T20:30:26Z",
+            ],
+            position_time: "2026-03-20T10:00:00Z",
+            altitude: 14.91,
+            azimuth: 123.40,
+        },
+        // Quito, equinox on the equator
+        NoaaReference {
+            coordinates: Coordinates {
+                latitude: -0.18,
+                longitude: -78.47,
+                altitude: 0.0,
+            },
+            time: "2026-09-23T17:00:00Z",
+            events: [
+                "2026-09-23T10:42:11Z",
+                "2026-09-23T11:02:51Z",
+                "2026-09-23T17:06:11Z",
+                "2026-09-23T23:09:31Z",
+                "2026-09-23T23:30:11Z",
+            ],
+            position_time: "2026-09-23T14:00:00Z",
+            altitude: 43.46,
+            azimuth: 90.14,
+        },
+    ];
+
+    #[test]
+    fn event_times_match_noaa() {
+        for reference in &NOAA_REFERENCES {
+            let time = timestamp(reference.time);
+            let coordinates = reference.coordinates;
+            let event = |angle, direction| time_at_angle(time, angle, direction, coordinates);
+
+            let [dawn, sunrise, noon, sunset, dusk] = reference.events;
+            assert_close(event(CIVIL_TWILIGHT_ANGLE, Direction::Rising), dawn, 60);
+            assert_close(event(SUNRISE_ANGLE, Direction::Rising), sunrise, 60);
+            assert_close(solar_noon(time, coordinates.longitude), noon, 60);
+            assert_close(event(SUNRISE_ANGLE, Direction::Setting), sunset, 60);
+            assert_close(event(CIVIL_TWILIGHT_ANGLE, Direction::Setting), dusk, 60);
+        }
+    }
+
+    #[test]
+    fn position_matches_noaa() {
+        for reference in &NOAA_REFERENCES {
+            let position = position(timestamp(reference.position_time), reference.coordinates);
+
+            // The spreadsheet shows two decimals
+            assert!(
+                (position.altitude - reference.altitude).abs() < 0.01,
+                "{position:?} at {}",
+                reference.time
+            );
+            assert!(
+                (position.azimuth - reference.azimuth).abs() < 0.01,
+                "{position:?} at {}",
+                reference.time
+            );
+        }
+    }
+
+    #[test]
+    fn position_follows_the_day() {
+        let boulder = coordinates(40.0, -105.0);
+
+        let noon = solar_noon(timestamp("2026-06-21T19:00:00Z"), boulder.longitude);
+        let position_at_noon = position(noon, boulder);
+        assert!(
+            (position_at_noon.azimuth - 180.0).abs() < 0.5,
+            "{position_at_noon:?}"
+        );
+
+        let morning = position(noon - jiff::SignedDuration::from_hours(4), boulder);
+        assert!(morning.azimuth < 180.0 && morning.altitude < position_at_noon.altitude);
+
+        let afternoon = position(noon + jiff::SignedDuration::from_hours(4), boulder);
+        assert!(afternoon.azimuth > 180.0 && afternoon.altitude < position_at_noon.altitude);
+    }
+
+    #[test]
+    fn position_at_sunrise_is_on_the_horizon() {
+        let kyiv = coordinates(50.5, 30.5);
+        let sunrise = time_at_angle(
+            timestamp("2013-03-05T00:00:00Z"),
+            SUNRISE_ANGLE,
+            Direction::Rising,
+            kyiv,
+        );
+
+        // Event angles are geometric, positions are corrected for refraction
+        let altitude = position(sunrise, kyiv).altitude;
+        let expected = SUNRISE_ANGLE + refraction(SUNRISE_ANGLE);
+        assert!((altitude - expected).abs() < 0.01, "{altitude}");
+    }
+
+    #[test]
+    fn polar_night_and_midnight_sun() {
+        let tromso = coordinates(69.65, 18.96);
+
+        let winter = timestamp("2026-12-21T12:00:00Z");
+        let noon = solar_noon(winter, tromso.longitude);
+        assert_eq!(
+            time_at_angle(winter, SUNRISE_ANGLE, Direction::Rising, tromso),
+            noon
+        );
+        assert_eq!(
+            time_at_angle(winter, SUNRISE_ANGLE, Direction::Setting, tromso),
+            noon
+        );
+
+        let summer = timestamp("2026-06-21T12:00:00Z");
+        let sunrise = time_at_angle(summer, SUNRISE_ANGLE, Direction::Rising, tromso);
+        let sunset = time_at_angle(summer, SUNRISE_ANGLE, Direction::Setting, tromso);
+        assert_close(sunrise, "2026-06-20T22:46:00Z", 60);
+        assert_close(sunset, "2026-06-21T22:46:00Z", 60);
+    }
+
+    #[test]
+    fn elevation_lowers_the_horizon() {
+        let sea_level = coordinates(46.0, 7.0);
+        let mountain = Coordinates {
+            altitude: 3000.0,
+            ..sea_level
+        };
+        let day = timestamp("2026-03-20T12:00:00Z");
+
+        let sunrise = time_at_angle(day, SUNRISE_ANGLE, Direction::Rising, sea_level);
+        let mountain_sunrise = time_at_angle(day, SUNRISE_ANGLE, Direction::Rising, mountain);
+
+        // A dip of about 1.6° is worth roughly ten minutes at this latitude
+        let earlier = sunrise.duration_since(mountain_sunrise).as_secs();
+        assert!((480..=720).contains(&earlier), "{earlier}");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    color::ColorSetting,
+    location::{ProviderState, Source},
+};
+use color_eyre::eyre::{WrapErr, eyre};
+use jiff::{Timestamp, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+};
+
+/// State of the running daemon, written to the runtime directory on every update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub updated: Timestamp,
+    pub temperature: u16,
+    pub brightness: f64,
+    /// Name of the schedule override active today
+    pub active_override: Option<String>,
+    /// Whether the location provider is delivering locations, the last known location is used
+    /// otherwise
+    pub location_provider: ProviderState,
+    /// Provider the location in use came from
+    #[serde(default)]
+    pub location_source: Option<Source>,
+    /// Outputs another gamma control client holds, which keep their colors
+    #[serde(default)]
+    pub blocked_outputs: Vec<String>,
+}
+
+impl Status {
+    pub fn new(
+        updated: Timestamp,
+        color: ColorSetting,
+        active_override: Option<String>,
+        location_provider: ProviderState,
+        location_source: Option<Source>,
+        blocked_outputs: Vec<String>,
+    ) -> Self {
+        Self {
+            updated,
+            temperature: color.temperature,
+            brightness: color.brightness,
+            active_override,
+            location_provider,
+            location_source,
+            blocked_outputs,
+        }
+    }
+
+    /// Atomically replace the status file
+    pub fn write(&self) -> color_eyre::Result<()> {
+        let path = status_path().ok_or_else(|| eyre!("No runtime directory available"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// Read the status of the daemon of this user, or else of the system service
+    pub fn read() -> color_eyre::Result<Self> {
+        let candidates: Vec<PathBuf> = status_path()
+            .into_iter()
+            .chain([PathBuf::from(SERVICE_RUNTIME_DIRECTORY).join("status.json")])
+            .collect();
+        let path = candidates
+            .iter()
+            .find(|path| path.exists())
+            .unwrap_or(&candidates[0]);
+        let contents = fs::read(path).wrap_err_with(|| {
+            format!("Failed to read {}, is the daemon running?", path.display())
+        })?;
+
+        Ok(serde_json::from_slice(&contents)?)
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color = ColorSetting {
+            temperature: self.temperature,
+            brightness: self.brightness,
+        };
+
+        writeln!(f, "Temperature: {color}")?;
+        writeln!(
+            f,
+            "Override: {}",
+            self.active_override.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "Location provider: {}", self.location_provider)?;
+        if let Some(source) = self.location_source {
+            writeln!(f, "Location source: {source}")?;
+        }
+        if !self.blocked_outputs.is_empty() {
+            writeln!(
+                f,
+                "Blocked by another gamma client: {}",
+                self.blocked_outputs.join(", ")
+            )?;
+        }
+        let updated = self.updated.to_zoned(TimeZone::system());
+        write!(f, "Updated: {}", updated.strftime("%Y-%m-%d %H:%M:%S"))
+    }
+}
+
+/// `RuntimeDirectory` of the system service, which runs as its own user
+const SERVICE_RUNTIME_DIRECTORY: &str = "/run/automatic-redshift";
+
+/// `$RUNTIME_DIRECTORY` when run as a systemd service, otherwise below `$XDG_RUNTIME_DIR`
+fn status_path() -> Option<PathBuf> {
+    let runtime_dir = env::var_os("RUNTIME_DIRECTORY")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("automatic-redshift"))
+        })?;
+
+    Some(runtime_dir.join("status.json"))
+}
@@ -0,0 +1,162 @@
+use crate::location::{
+    Coordinates, Fix, LocationEvent, LocationProvider, ProviderState, Source, constant,
+};
+use color_eyre::eyre::{OptionExt, bail, eyre};
+use futures::{
+    StreamExt,
+    stream::{self, LocalBoxStream},
+};
+use jiff::{Timestamp, tz::TimeZone};
+use log::warn;
+use std::{env, fs, path::PathBuf};
+
+/// Tables of the tz database listing the principal location of every zone, the first one is
+/// more complete for zones merged since 1970
+const ZONE_TABLES: [&str; 2] = ["zone1970.tab", "zone.tab"];
+
+/// Rough location from the system time zone, the principal city of the zone according to the tz
+/// database
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeZoneProvider;
+
+impl LocationProvider for TimeZoneProvider {
+    fn source(&self) -> Source {
+        Source::TimeZone
+    }
+
+    fn locations(&self) -> LocalBoxStream<'static, LocationEvent> {
+        match time_zone_coordinates() {
+            Ok(coordinates) => constant(Fix {
+                coordinates,
+                source: Source::TimeZone,
+                accuracy: None,
+                timestamp: Timestamp::now(),
+            }),
+            Err(err) => {
+                warn!("No location for the time zone: {err}");
+                stream::iter([LocationEvent::State(ProviderState::Disconnected)]).boxed_local()
+            }
+        }
+    }
+}
+
+fn time_zone_coordinates() -> color_eyre::Result<Coordinates> {
+    let timezone = TimeZone::system();
+    let name = timezone
+        .iana_name()
+        .ok_or_eyre("The system time zone has no name")?;
+
+    let zoneinfo = env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"));
+
+    for table in ZONE_TABLES {
+        let Ok(contents) = fs::read_to_string(zoneinfo.join(table)) else {
+            continue;
+        };
+
+        // Country codes, coordinates, zone name and an optional comment separated by tabs
+        let coordinates = contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .find(|fields| fields.get(2) == Some(&name))
+            .map(|fields| parse_iso6709(fields[1]));
+
+        if let Some(coordinates) = coordinates {
+            return coordinates;
+        }
+    }
+
+    Err(eyre!(
+        "Time zone {name} not found in {}",
+        zoneinfo.display()
+    ))
+}
+
+/// Coordinates in the ISO 6709 form of the tz database, `±DDMM±DDDMM` or `±DDMMSS±DDDMMSS`
+fn parse_iso6709(value: &str) -> color_eyre::Result<Coordinates> {
+    let split = value
+        .get(1..)
+        .and_then(|rest| rest.find(['+', '-']))
+        .map(|index| index + 1)
+        .ok_or_else(|| eyre!("Invalid coordinates {value}"))?;
+    let (latitude, longitude) = value.split_at(split);
+
+    Ok(Coordinates {
+        latitude: parse_angle(latitude, 2)?,
+        longitude: parse_angle(longitude, 3)?,
+        altitude: 0.0,
+    })
+}
+
+/// Signed angle with `degree_digits` digits of degrees followed by minutes and optional seconds
+fn parse_angle(value: &str, degree_digits: usize) -> color_eyre::Result<f64> {
+    let sign = match value.as_bytes().first() {
+        Some(b'+') => 1.0,
+        Some(b'-') => -1.0,
+        _ => bail!("Invalid angle {value}"),
+    };
+    let digits = &value[1..];
+    let valid_length = matches!(digits.len().checked_sub(degree_digits), Some(2 | 4));
+    if !valid_length || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        bail!("Invalid angle {value}");
+    }
+
+    let (degrees, rest) = digits.split_at(degree_digits);
+    let (minutes, seconds) = rest.split_at(2);
+    let degrees: f64 = degrees.parse()?;
+    let minutes: f64 = minutes.parse()?;
+    let seconds: f64 = if seconds.is_empty() {
+        0.0
+    } else {
+        seconds.parse()?
+    };
+
+    Ok(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_coordinates(value: &str, latitude: f64, longitude: f64) {
+        let coordinates = parse_iso6709(value).unwrap();
+        assert!(
+            (coordinates.latitude - latitude).abs() < 1e-9
+                && (coordinates.longitude - longitude).abs() < 1e-9,
+            "{value}: {coordinates:?}"
+        );
+    }
+
+    /// Samples of zone1970.tab
+    #[test]
+    fn parses_zone_table_coordinates() {
+        // Europe/Berlin and Australia/Sydney, degrees and minutes
+        assert_coordinates("+5230+01322", 52.5, 13.0 + 22.0 / 60.0);
+        assert_coordinates("-3352+15113", -(33.0 + 52.0 / 60.0), 151.0 + 13.0 / 60.0);
+        // America/Sao_Paulo, both negative
+        assert_coordinates("-2332-04637", -(23.0 + 32.0 / 60.0), -(46.0 + 37.0 / 60.0));
+        // America/New_York and Pacific/Honolulu, with seconds
+        assert_coordinates(
+            "+404251-0740023",
+            40.0 + 42.0 / 60.0 + 51.0 / 3600.0,
+            -(74.0 + 23.0 / 3600.0),
+        );
+        assert_coordinates(
+            "+211825-1575130",
+            21.0 + 18.0 / 60.0 + 25.0 / 3600.0,
+            -(157.0 + 51.0 / 60.0 + 30.0 / 3600.0),
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_coordinates() {
+        assert!(parse_iso6709("").is_err());
+        assert!(parse_iso6709("+5230").is_err());
+        assert!(parse_iso6709("5230+01322").is_err());
+        assert!(parse_iso6709("+523+01322").is_err());
+        assert!(parse_iso6709("+5230+0132").is_err());
+        assert!(parse_iso6709("+52a0+01322").is_err());
+    }
+}
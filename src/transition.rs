@@ -0,0 +1,188 @@
+use crate::color::ColorSetting;
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+/// How the ramps between keyframes are shaped and interpolated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transition {
+    pub easing: Easing,
+    pub interpolation: Interpolation,
+}
+
+impl Transition {
+    /// Interpolate between two color settings, `progress` being in the range `0.0..=1.0`
+    pub fn interpolate(
+        &self,
+        progress: f64,
+        start: ColorSetting,
+        stop: ColorSetting,
+    ) -> ColorSetting {
+        let progress = self.easing.apply(progress.clamp(0.0, 1.0));
+
+        ColorSetting {
+            temperature: self.interpolation.interpolate(
+                progress,
+                start.temperature,
+                stop.temperature,
+            ),
+            brightness: start.brightness + (stop.brightness - start.brightness) * progress,
+        }
+    }
+}
+
+/// Easing curve applied to the transition progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Slow start and end, see <https://en.wikipedia.org/wiki/Smoothstep>
+    Smoothstep,
+    /// Half a cosine period, starting and ending slightly slower than smoothstep
+    Cosine,
+    /// Exponential ease-in-out, most of the change happens mid-transition
+    Exponential,
+}
+
+impl Easing {
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::Cosine => (1.0 - (t * PI).cos()) / 2.0,
+            Easing::Exponential => {
+                if t <= 0.0 {
+                    0.0
+                } else if t >= 1.0 {
+                    1.0
+                } else if t < 0.5 {
+                    2f64.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f64.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// Color space the temperature is interpolated in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interpolation {
+    /// Linear in Kelvin
+    #[default]
+    Kelvin,
+    /// Linear in [mired](https://en.wikipedia.org/wiki/Mired) (1e6 / K), which is closer to
+    /// perceptually uniform
+    Mired,
+}
+
+impl Interpolation {
+    fn interpolate(self, progress: f64, temp_start: u16, temp_stop: u16) -> u16 {
+        let (start, stop) = (temp_start as f64, temp_stop as f64);
+
+        match self {
+            // Truncated like before interpolation was configurable, so the default schedule is
+            // unchanged
+            Interpolation::Kelvin => (start + (stop - start) * progress) as u16,
+            Interpolation::Mired => {
+                let (mired_start, mired_stop) = (1e6 / start, 1e6 / stop);
+                (1e6 / (mired_start + (mired_stop - mired_start) * progress)).round() as u16
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::Smoothstep,
+        Easing::Cosine,
+        Easing::Exponential,
+    ];
+
+    #[test]
+    fn easings_start_and_end_at_the_endpoints() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-12, "{easing:?}");
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-12, "{easing:?}");
+        }
+    }
+
+    #[test]
+    fn easings_are_monotonic() {
+        for easing in EASINGS {
+            let mut previous = easing.apply(0.0);
+            for step in 1..=1000 {
+                let value = easing.apply(step as f64 / 1000.0);
+                assert!(value >= previous, "{easing:?} decreases at step {step}");
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn easings_other_than_linear_start_slowly() {
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        for easing in [Easing::Smoothstep, Easing::Cosine, Easing::Exponential] {
+            assert!(easing.apply(0.25) < 0.25, "{easing:?}");
+            assert!(easing.apply(0.75) > 0.75, "{easing:?}");
+        }
+        // Exponential leaves the most of the change for mid-transition
+        assert!(Easing::Exponential.apply(0.1) < Easing::Cosine.apply(0.1));
+        assert!(Easing::Exponential.apply(0.1) < Easing::Smoothstep.apply(0.1));
+    }
+
+    #[test]
+    fn transitions_reach_both_colors() {
+        let day = ColorSetting::new(6500);
+        let night = ColorSetting {
+            temperature: 4000,
+            brightness: 0.7,
+        };
+
+        for easing in EASINGS {
+            for interpolation in [Interpolation::Kelvin, Interpolation::Mired] {
+                let transition = Transition {
+                    easing,
+                    interpolation,
+                };
+                assert_eq!(transition.interpolate(0.0, day, night), day);
+                assert_eq!(transition.interpolate(1.0, day, night), night);
+                assert_eq!(transition.interpolate(1.0, night, day), day);
+                // Progress outside the transition is clamped
+                assert_eq!(transition.interpolate(-0.5, day, night), day);
+                assert_eq!(transition.interpolate(1.5, day, night), night);
+            }
+        }
+    }
+
+    #[test]
+    fn kelvin_interpolation_truncates() {
+        assert_eq!(
+            Interpolation::Kelvin.interpolate(1.0 / 3.0, 6500, 4000),
+            5666
+        );
+        assert_eq!(
+            Interpolation::Kelvin.interpolate(1.0 / 3.0, 4000, 6500),
+            4833
+        );
+        assert_eq!(Interpolation::Kelvin.interpolate(0.5, 6500, 4000), 5250);
+    }
+
+    #[test]
+    fn mired_interpolation_is_warmer_at_the_midpoint() {
+        let kelvin = Interpolation::Kelvin.interpolate(0.5, 6500, 4000);
+        let mired = Interpolation::Mired.interpolate(0.5, 6500, 4000);
+
+        // Halfway between 153.8 and 250 mired
+        assert_eq!(mired, 4952);
+        assert!(mired < kelvin);
+        assert_eq!(Interpolation::Mired.interpolate(0.5, 4000, 6500), 4952);
+    }
+}
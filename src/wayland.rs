@@ -1,8 +1,20 @@
-use crate::color::fill_colorramp;
+use crate::{backoff::Backoff, color::ColorSetting, config::OutputConfig, ramp::RampCache};
 use color_eyre::eyre::bail;
-use log::{debug, info};
-use std::os::fd::{AsRawFd, RawFd};
-use tokio::io::{Interest, unix::AsyncFd};
+use log::{debug, info, warn};
+use std::{
+    env,
+    ffi::OsString,
+    fmt::{self, Display},
+    fs, io, mem,
+    os::unix::fs::FileTypeExt,
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::{
+    io::{Interest, unix::AsyncFd},
+    select,
+    time::{Instant, sleep_until},
+};
 use wayrs_client::{
     Connection, EventCtx, IoMode,
     global::{Global, GlobalExt},
@@ -12,19 +24,160 @@ use wayrs_protocols::wlr_gamma_control_unstable_v1::{
     ZwlrGammaControlManagerV1, ZwlrGammaControlV1, zwlr_gamma_control_v1,
 };
 
+/// How long to wait between attempts to reconnect to the compositor
+const RECONNECT_BACKOFF: Backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+
+/// How long to wait before asking again for the gamma control of an output another client holds
+const CONFLICT_BACKOFF: Backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(60));
+/// Other gamma control clients, to name the one holding an output
+const GAMMA_CLIENTS: [&str; 7] = [
+    "gammastep",
+    "wlsunset",
+    "redshift",
+    "hyprsunset",
+    "sunsetr",
+    "wl-gammarelay",
+    "wl-gammarelay-rs",
+];
+/// Failed updates in a row after which an output is left alone
+const MAX_OUTPUT_FAILURES: u32 = 3;
+
+/// Gamma control of all outputs, connecting once the compositor is there and reconnecting when it
+/// goes away
 pub struct Wayland {
-    conn: AsyncFd<Connection<WaylandState>>,
-    state: WaylandState,
+    /// `None` until connected and while the compositor is gone
+    session: Option<Session>,
+    /// Whether a connection was ever established, for logging
+    connected_before: bool,
+    /// Whether the last attempt to connect failed, so only the first failure is a warning
+    failing: bool,
+    /// Temperature to apply, kept across reconnects
+    color: ColorSetting,
+    /// Settings of the outputs matching them
+    configs: Vec<OutputConfig>,
+    backoff: Backoff,
+    next_attempt: Instant,
 }
 
-impl AsRawFd for Wayland {
-    fn as_raw_fd(&self) -> RawFd {
-        self.conn.as_raw_fd()
+impl Wayland {
+    /// Connects on the first [`poll`](Self::poll), so a compositor that isn't running yet is
+    /// waited for
+    pub fn new(configs: Vec<OutputConfig>) -> Self {
+        Self {
+            session: None,
+            connected_before: false,
+            failing: false,
+            color: ColorSetting::new(6500),
+            configs,
+            backoff: RECONNECT_BACKOFF,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    /// Apply `color` to all outputs, retrying outputs that failed before
+    pub fn set_temperature(&mut self, color: ColorSetting) {
+        self.color = color;
+
+        let Some(session) = &mut self.session else {
+            debug!("Not connected to the compositor, applying {color} once connected");
+            return;
+        };
+
+        if let Err(err) = session.set_temperature(color) {
+            self.disconnected(err);
+        }
+    }
+
+    /// Handle events of the compositor, or connect to it if it isn't yet or went away
+    ///
+    /// Returns the errors of single outputs since the last call, the other outputs keep working.
+    /// Cancel safe, so it can be polled in `select!`.
+    pub async fn poll(&mut self) -> Vec<OutputError> {
+        // Errors of the last update are reported right away instead of with the next event
+        if let Some(session) = &mut self.session
+            && !session.state.errors.is_empty()
+        {
+            return mem::take(&mut session.state.errors);
+        }
+
+        match &mut self.session {
+            Some(session) => {
+                if let Err(err) = session.poll().await {
+                    self.disconnected(err);
+                }
+            }
+            None => {
+                sleep_until(self.next_attempt).await;
+
+                if let Some(display) = find_display() {
+                    info!("Wayland socket is gone, switching to {}", display.display());
+                    // SAFETY: Nothing else in the daemon changes the environment, and glibc keeps
+                    // replaced environments alive, so helper threads reading it concurrently, like
+                    // DNS lookups of the IP geolocation provider, still see a valid one
+                    unsafe { env::set_var("WAYLAND_DISPLAY", &display) };
+                }
+
+                match Session::connect(self.color, self.configs.clone()) {
+                    Ok(session) => {
+                        if self.connected_before {
+                            info!("Reconnected to the Wayland compositor");
+                        } else {
+                            info!("Connected to the Wayland compositor");
+                        }
+                        self.session = Some(session);
+                        self.connected_before = true;
+                        self.failing = false;
+                        self.backoff.reset();
+                    }
+                    Err(err) => {
+                        let delay = self.backoff.next_delay();
+                        if self.failing {
+                            debug!(
+                                "Failed to connect to the Wayland compositor, retrying in {delay:?}: {err}"
+                            );
+                        } else {
+                            warn!(
+                                "Failed to connect to the Wayland compositor, retrying in {delay:?}: {err}"
+                            );
+                        }
+                        self.failing = true;
+                        self.next_attempt = Instant::now() + delay;
+                    }
+                }
+            }
+        }
+
+        self.session
+            .as_mut()
+            .map(|session| mem::take(&mut session.state.errors))
+            .unwrap_or_default()
+    }
+
+    /// Outputs another client holds the gamma control of
+    pub fn conflicts(&self) -> Vec<String> {
+        self.session
+            .iter()
+            .flat_map(|session| &session.state.outputs)
+            .filter_map(Output::conflict)
+            .collect()
+    }
+
+    fn disconnected(&mut self, err: io::Error) {
+        warn!("Lost connection to the Wayland compositor: {err}");
+        self.session = None;
+        self.next_attempt = Instant::now() + self.backoff.next_delay();
     }
 }
 
-impl Wayland {
-    pub fn new() -> color_eyre::Result<Self> {
+/// A connection to the compositor and the outputs bound through it
+struct Session {
+    conn: AsyncFd<Connection<WaylandState>>,
+    state: WaylandState,
+}
+
+impl Session {
+    /// Connect and apply `color` to the outputs as they appear
+    fn connect(color: ColorSetting, configs: Vec<OutputConfig>) -> color_eyre::Result<Self> {
         let mut conn = Connection::connect()?;
         conn.blocking_roundtrip()?;
 
@@ -37,7 +190,10 @@ impl Wayland {
         let mut state = WaylandState {
             outputs: Vec::new(),
             gamma_manager,
-            temperature: 6500,
+            color,
+            errors: Vec::new(),
+            ramps: RampCache::default(),
+            configs,
         };
 
         conn.add_registry_cb(wl_registry_cb);
@@ -49,56 +205,204 @@ impl Wayland {
         Ok(Self { conn, state })
     }
 
-    pub fn set_temperature(&mut self, temperature: u16) -> color_eyre::Result<()> {
-        let did_change = self.state.set_temperature(temperature);
-
-        if did_change {
-            self.conn.get_mut().dispatch_events(&mut self.state);
+    fn set_temperature(&mut self, color: ColorSetting) -> io::Result<()> {
+        self.state.set_temperature(color);
+        self.conn.get_mut().dispatch_events(&mut self.state);
 
-            for output in &mut self.state.outputs {
-                debug!("Output {}: updating displayed temperature", output.reg_name);
-                output.update_displayed_temperature(self.state.temperature, self.conn.get_mut())?;
-            }
-
-            self.conn.get_mut().flush(IoMode::Blocking)?;
+        // Outputs that already show the color are skipped, failed ones are retried
+        let state = &mut self.state;
+        for output in &mut state.outputs {
+            state
+                .errors
+                .extend(output.apply(state.color, &mut state.ramps, self.conn.get_mut()));
         }
 
-        Ok(())
+        self.conn.get_mut().flush(IoMode::Blocking)
     }
 
-    pub async fn poll(&mut self) -> color_eyre::Result<()> {
-        self.conn
-            .async_io_mut(Interest::READABLE, |inner| {
+    async fn poll(&mut self) -> io::Result<()> {
+        let retry_at = self
+            .state
+            .outputs
+            .iter()
+            .filter_map(|output| output.conflict.as_ref()?.retry_at)
+            .min();
+
+        select! {
+            result = self.conn.async_io_mut(Interest::READABLE, |inner| {
                 inner.recv_events(IoMode::NonBlocking)
-            })
-            .await?;
+            }) => result?,
+            _ = sleep_until(retry_at.unwrap_or_else(Instant::now)), if retry_at.is_some() => {
+                let now = Instant::now();
+                for output in &mut self.state.outputs {
+                    let retry_at = output.conflict.as_ref().and_then(|conflict| conflict.retry_at);
+                    if retry_at.is_some_and(|retry_at| retry_at <= now) {
+                        output.request_gamma_control(self.conn.get_mut(), self.state.gamma_manager);
+                    }
+                }
+            }
+        }
 
-        Ok(())
+        // Outputs that appeared get the current temperature once their ramp size is known
+        self.conn.get_mut().dispatch_events(&mut self.state);
+        self.conn.get_mut().flush(IoMode::Blocking)
     }
 }
 
 pub struct WaylandState {
     pub outputs: Vec<Output>,
     pub gamma_manager: ZwlrGammaControlManagerV1,
-    pub temperature: u16,
+    pub color: ColorSetting,
+    /// Errors of single outputs raised in callbacks, reported by `Wayland::poll`
+    pub errors: Vec<OutputError>,
+    pub ramps: RampCache,
+    /// Settings of the outputs matching them, the first match wins
+    pub configs: Vec<OutputConfig>,
 }
 
 impl WaylandState {
-    #[must_use]
-    pub fn set_temperature(&mut self, temperature: u16) -> bool {
-        if temperature != self.temperature {
-            debug!(
-                "Temperature changed from {:?} to {:?}",
-                self.temperature, temperature
-            );
-            self.temperature = temperature;
-
-            true
+    pub fn set_temperature(&mut self, color: ColorSetting) {
+        if color != self.color {
+            debug!("Temperature changed from {} to {}", self.color, color);
+            self.color = color;
+            self.ramps.clear();
         } else {
-            debug!("Temperature unchanged {:?}", temperature);
+            debug!("Temperature unchanged {}", color);
+        }
+    }
+
+    /// Settings of the first matching output configuration, the defaults without one
+    fn output_config(&self, info: &OutputInfo) -> OutputConfig {
+        self.configs
+            .iter()
+            .find(|config| config.matches(info))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// What the compositor reports about an output, for matching it in the configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputInfo {
+    /// Connector name, like `DP-1`
+    pub name: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    /// Human readable description, usually including make, model and serial number
+    pub description: Option<String>,
+    /// Width and height in millimeters, if known
+    pub physical_size: Option<(i32, i32)>,
+    pub mode: Option<OutputMode>,
+}
+
+/// Current video mode of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputMode {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in mHz
+    pub refresh: i32,
+}
 
-            false
+impl OutputInfo {
+    /// Record a metadata event, returns whether it was `done`, which ends a batch of changes
+    fn update(&mut self, event: &wl_output::Event) -> bool {
+        match event {
+            wl_output::Event::Geometry(geometry) => {
+                self.make = Some(String::from_utf8_lossy(geometry.make.as_bytes()).into_owned());
+                self.model = Some(String::from_utf8_lossy(geometry.model.as_bytes()).into_owned());
+                self.physical_size = (geometry.physical_width > 0 && geometry.physical_height > 0)
+                    .then_some((geometry.physical_width, geometry.physical_height));
+            }
+            wl_output::Event::Mode(mode) if mode.flags.contains(wl_output::Mode::Current) => {
+                self.mode = Some(OutputMode {
+                    width: mode.width,
+                    height: mode.height,
+                    refresh: mode.refresh,
+                });
+            }
+            wl_output::Event::Name(name) => {
+                self.name = Some(String::from_utf8_lossy(name.as_bytes()).into_owned());
+            }
+            wl_output::Event::Description(description) => {
+                self.description =
+                    Some(String::from_utf8_lossy(description.as_bytes()).into_owned());
+            }
+            wl_output::Event::Done => return true,
+            _ => (),
+        }
+
+        false
+    }
+}
+
+impl Display for OutputInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = "unknown";
+
+        writeln!(f, "{}", self.name.as_deref().unwrap_or(unknown))?;
+        writeln!(f, "  Make: {:?}", self.make.as_deref().unwrap_or(unknown))?;
+        writeln!(f, "  Model: {:?}", self.model.as_deref().unwrap_or(unknown))?;
+        writeln!(
+            f,
+            "  Description: {:?}",
+            self.description.as_deref().unwrap_or(unknown)
+        )?;
+        match self.mode {
+            Some(mode) => writeln!(
+                f,
+                "  Mode: {}x{} @ {:.3} Hz",
+                mode.width,
+                mode.height,
+                f64::from(mode.refresh) / 1000.0
+            )?,
+            None => writeln!(f, "  Mode: {unknown}")?,
         }
+        match self.physical_size {
+            Some((width, height)) => write!(f, "  Physical size: {width}x{height} mm"),
+            None => write!(f, "  Physical size: {unknown}"),
+        }
+    }
+}
+
+/// Metadata of all outputs, without taking over their gamma control
+pub fn list_outputs() -> color_eyre::Result<Vec<OutputInfo>> {
+    let mut conn = Connection::<Vec<(WlOutput, OutputInfo)>>::connect()?;
+    conn.blocking_roundtrip()?;
+    let globals = conn.globals().to_vec();
+
+    let mut outputs = Vec::new();
+    for global in globals.iter().filter(|global| global.is::<WlOutput>()) {
+        let wl = global.bind_with_cb(
+            &mut conn,
+            4,
+            |ctx: EventCtx<Vec<(WlOutput, OutputInfo)>, WlOutput>| {
+                if let Some((_, info)) = ctx.state.iter_mut().find(|(wl, _)| *wl == ctx.proxy) {
+                    info.update(&ctx.event);
+                }
+            },
+        )?;
+        outputs.push((wl, OutputInfo::default()));
+    }
+
+    // The compositor sends the metadata right after binding
+    conn.blocking_roundtrip()?;
+    conn.dispatch_events(&mut outputs);
+
+    Ok(outputs.into_iter().map(|(_, info)| info).collect())
+}
+
+/// Failure of a single output
+#[derive(Debug)]
+pub struct OutputError {
+    /// Connector name, or the registry name if the compositor didn't send one yet
+    pub output: String,
+    pub error: color_eyre::Report,
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output {}: {}", self.output, self.error)
     }
 }
 
@@ -106,40 +410,200 @@ impl WaylandState {
 pub struct Output {
     reg_name: u32,
     wl: WlOutput,
-    name: Option<String>,
-    gamma_control: ZwlrGammaControlV1,
+    info: OutputInfo,
+    /// Settings matched once the compositor sent the metadata
+    config: Option<OutputConfig>,
+    /// `None` until the output is configured, while another client holds the gamma control and
+    /// for disabled outputs
+    gamma_control: Option<ZwlrGammaControlV1>,
     ramp_size: usize,
+    /// Color of the ramp the compositor was last given
+    applied: Option<ColorSetting>,
+    /// Failed updates in a row, retried on the next update
+    failures: u32,
+    /// Set while another client holds the gamma control
+    conflict: Option<Conflict>,
+    backoff: Backoff,
+}
+
+/// Another client holds the gamma control of an output
+#[derive(Debug)]
+struct Conflict {
+    /// Name of the client, if it could be found
+    holder: Option<String>,
+    /// `None` while waiting for the answer to a request
+    retry_at: Option<Instant>,
 }
 
 impl Output {
-    fn bind(
-        conn: &mut Connection<WaylandState>,
-        global: &Global,
-        gamma_manager: ZwlrGammaControlManagerV1,
-    ) -> color_eyre::Result<Self> {
+    fn bind(conn: &mut Connection<WaylandState>, global: &Global) -> color_eyre::Result<Self> {
         info!("New output: {}", global.name);
         let output = global.bind_with_cb(conn, 4, wl_output_cb)?;
 
         Ok(Self {
             reg_name: global.name,
             wl: output,
-            name: None,
-            gamma_control: gamma_manager.get_gamma_control_with_cb(conn, output, gamma_control_cb),
+            info: OutputInfo::default(),
+            config: None,
+            gamma_control: None,
             ramp_size: 0,
+            applied: None,
+            failures: 0,
+            conflict: None,
+            backoff: CONFLICT_BACKOFF,
         })
     }
 
     fn destroy(self, conn: &mut Connection<WaylandState>) {
         info!("Output {} removed", self.reg_name);
-        self.gamma_control.destroy(conn);
+        if let Some(gamma_control) = self.gamma_control {
+            gamma_control.destroy(conn);
+        }
         self.wl.release(conn);
     }
 
+    /// Apply the settings matching the metadata, taking over or releasing the gamma control
+    fn configure(
+        &mut self,
+        config: OutputConfig,
+        conn: &mut Connection<WaylandState>,
+        gamma_manager: ZwlrGammaControlManagerV1,
+    ) {
+        if self.config.as_ref() != Some(&config) {
+            debug!("Output {}: {config:?}", self.label());
+        }
+
+        if config.enabled {
+            if self.gamma_control.is_none() && self.conflict.is_none() {
+                self.request_gamma_control(conn, gamma_manager);
+            }
+        } else if self.config.as_ref().is_none_or(|config| config.enabled) {
+            info!("Output {}: disabled in the configuration", self.label());
+            // The compositor restores the original gamma once the control is gone
+            if let Some(gamma_control) = self.gamma_control.take() {
+                gamma_control.destroy(conn);
+            }
+            self.ramp_size = 0;
+            self.applied = None;
+            self.conflict = None;
+        }
+
+        self.config = Some(config);
+    }
+
+    /// Ask for the gamma control again, the compositor answers with the ramp size or fails again
+    fn request_gamma_control(
+        &mut self,
+        conn: &mut Connection<WaylandState>,
+        gamma_manager: ZwlrGammaControlManagerV1,
+    ) {
+        debug!("Output {}: requesting gamma control", self.label());
+        self.gamma_control =
+            Some(gamma_manager.get_gamma_control_with_cb(conn, self.wl, gamma_control_cb));
+        if let Some(conflict) = &mut self.conflict {
+            conflict.retry_at = None;
+        }
+    }
+
+    /// The compositor refused the gamma control, most likely because another client holds it
+    fn gamma_control_failed(&mut self, conn: &mut Connection<WaylandState>) {
+        if let Some(gamma_control) = self.gamma_control.take() {
+            gamma_control.destroy(conn);
+        }
+        self.ramp_size = 0;
+        self.applied = None;
+
+        let delay = self.backoff.next_delay();
+        let holder = find_gamma_client();
+        match &holder {
+            Some(holder) => warn!(
+                "Output {}: gamma control is held by {holder}, retrying in {delay:?}",
+                self.label()
+            ),
+            None => warn!(
+                "Output {}: gamma control is held by another client, retrying in {delay:?}",
+                self.label()
+            ),
+        }
+
+        self.conflict = Some(Conflict {
+            holder,
+            retry_at: Some(Instant::now() + delay),
+        });
+    }
+
+    /// Description of the conflict, if another client holds the gamma control
+    fn conflict(&self) -> Option<String> {
+        let conflict = self.conflict.as_ref()?;
+
+        Some(match &conflict.holder {
+            Some(holder) => format!("{} (held by {holder})", self.label()),
+            None => self.label(),
+        })
+    }
+
+    /// Name to report the output by
+    fn label(&self) -> String {
+        self.info
+            .name
+            .clone()
+            .unwrap_or_else(|| self.reg_name.to_string())
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.failures >= MAX_OUTPUT_FAILURES
+    }
+
+    /// Update the gamma ramp unless the output failed too often, returning the error otherwise
+    fn apply(
+        &mut self,
+        color: ColorSetting,
+        ramps: &mut RampCache,
+        conn: &mut Connection<WaylandState>,
+    ) -> Option<OutputError> {
+        let Some(config) = &self.config else {
+            return None;
+        };
+        let color = ColorSetting {
+            brightness: color.brightness * config.brightness,
+            ..color
+        };
+        if self.is_disabled() || self.applied == Some(color) {
+            return None;
+        }
+
+        match self.update_displayed_temperature(color, ramps, conn) {
+            Ok(()) => {
+                self.failures = 0;
+                None
+            }
+            Err(error) => {
+                self.failures += 1;
+                if self.is_disabled() {
+                    warn!(
+                        "Output {}: disabled after {} failed updates",
+                        self.label(),
+                        self.failures
+                    );
+                }
+
+                Some(OutputError {
+                    output: self.label(),
+                    error,
+                })
+            }
+        }
+    }
+
     fn update_displayed_temperature(
         &mut self,
-        temperature: u16,
+        color: ColorSetting,
+        ramps: &mut RampCache,
         conn: &mut Connection<WaylandState>,
     ) -> color_eyre::Result<()> {
+        let Some(gamma_control) = self.gamma_control else {
+            return Ok(());
+        };
         if self.ramp_size == 0 {
             debug!(
                 "Output {}: skipping gamma update, ramp_size is 0",
@@ -149,23 +613,18 @@ impl Output {
         }
 
         debug!(
-            "Output {}: updating gamma ramp with temperature {:?}, ramp_size {}",
-            self.reg_name, temperature, self.ramp_size
+            "Output {}: updating gamma ramp with temperature {}, ramp_size {}",
+            self.reg_name, color, self.ramp_size
         );
 
-        let file = shmemfdrs2::create_shmem(c"/ramp-buffer")?;
-        file.set_len(self.ramp_size as u64 * 6)?;
-        let mut mmap = unsafe { memmap2::MmapMut::map_mut(&file)? };
-        let buf = bytemuck::cast_slice_mut::<u8, u16>(&mut mmap);
-        let (r, rest) = buf.split_at_mut(self.ramp_size);
-        let (g, b) = rest.split_at_mut(self.ramp_size);
-        fill_colorramp(r, g, b, self.ramp_size, temperature)?;
+        let ramp = ramps.get(self.ramp_size, color)?;
 
         debug!(
-            "Output {}: setting gamma ramp with temp {} K",
-            self.reg_name, temperature
+            "Output {}: setting gamma ramp with temp {}",
+            self.reg_name, color
         );
-        self.gamma_control.set_gamma(conn, file.into());
+        gamma_control.set_gamma(conn, ramp.open()?);
+        self.applied = Some(color);
 
         debug!("Output {}: gamma ramp update completed", self.reg_name);
         Ok(())
@@ -179,11 +638,14 @@ fn wl_registry_cb(
 ) {
     match event {
         wl_registry::Event::Global(global) if global.is::<WlOutput>() => {
-            let mut output = Output::bind(conn, global, state.gamma_manager).unwrap();
-            output
-                .update_displayed_temperature(state.temperature, conn)
-                .unwrap();
-            state.outputs.push(output);
+            match Output::bind(conn, global) {
+                // The gamma control is requested once the compositor sent the metadata
+                Ok(output) => state.outputs.push(output),
+                Err(error) => state.errors.push(OutputError {
+                    output: global.name.to_string(),
+                    error,
+                }),
+            }
         }
         wl_registry::Event::GlobalRemove(name) => {
             if let Some(output_index) = state.outputs.iter().position(|o| o.reg_name == *name) {
@@ -196,27 +658,34 @@ fn wl_registry_cb(
 }
 
 fn gamma_control_cb(ctx: EventCtx<WaylandState, ZwlrGammaControlV1>) {
-    let output_index = ctx
+    // Events may still arrive for an output that was just removed
+    let Some(output_index) = ctx
         .state
         .outputs
         .iter()
-        .position(|o| o.gamma_control == ctx.proxy)
-        .expect("Received event for unknown output");
+        .position(|o| o.gamma_control == Some(ctx.proxy))
+    else {
+        debug!("Ignoring gamma control event for a removed output");
+        return;
+    };
 
     match ctx.event {
         zwlr_gamma_control_v1::Event::GammaSize(size) => {
             let output = &mut ctx.state.outputs[output_index];
             debug!("Output {}: ramp_size = {}", output.reg_name, size);
             output.ramp_size = size as usize;
-            output
-                .update_displayed_temperature(ctx.state.temperature, ctx.conn)
-                .unwrap();
+            output.applied = None;
+            if output.conflict.take().is_some() {
+                info!("Output {}: took over the gamma control", output.label());
+            }
+            output.backoff.reset();
+            ctx.state
+                .errors
+                .extend(output.apply(ctx.state.color, &mut ctx.state.ramps, ctx.conn));
         }
 
         zwlr_gamma_control_v1::Event::Failed => {
-            let output = ctx.state.outputs.swap_remove(output_index);
-            info!("Output {}: gamma_control::Event::Failed", output.reg_name);
-            output.destroy(ctx.conn);
+            ctx.state.outputs[output_index].gamma_control_failed(ctx.conn);
         }
 
         _ => (),
@@ -224,16 +693,73 @@ fn gamma_control_cb(ctx: EventCtx<WaylandState, ZwlrGammaControlV1>) {
 }
 
 fn wl_output_cb(ctx: EventCtx<WaylandState, WlOutput>) {
-    if let wl_output::Event::Name(name) = ctx.event {
-        let output = ctx
-            .state
-            .outputs
-            .iter_mut()
-            .find(|o| o.wl == ctx.proxy)
-            .unwrap();
+    let Some(output_index) = ctx.state.outputs.iter().position(|o| o.wl == ctx.proxy) else {
+        debug!("Ignoring metadata of a removed output");
+        return;
+    };
+
+    let output = &mut ctx.state.outputs[output_index];
+    if !output.info.update(&ctx.event) {
+        return;
+    }
+    debug!("Output {}: {:?}", output.reg_name, output.info);
 
-        let name = String::from_utf8(name.into_bytes()).expect("invalid output name");
-        debug!("Output {}: name = {name:?}", output.reg_name);
-        output.name = Some(name);
+    let config = ctx
+        .state
+        .output_config(&ctx.state.outputs[output_index].info);
+    let output = &mut ctx.state.outputs[output_index];
+    output.configure(config, ctx.conn, ctx.state.gamma_manager);
+    ctx.state
+        .errors
+        .extend(output.apply(ctx.state.color, &mut ctx.state.ramps, ctx.conn));
+}
+
+/// Socket of another compositor in `$XDG_RUNTIME_DIR` once the one of `$WAYLAND_DISPLAY` is
+/// gone, for example after logging out and in again
+///
+/// `None` while the socket is there or when connecting through `$WAYLAND_SOCKET`.
+fn find_display() -> Option<OsString> {
+    if env::var_os("WAYLAND_SOCKET").is_some() {
+        return None;
+    }
+    let runtime_dir = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    if let Some(display) = env::var_os("WAYLAND_DISPLAY")
+        && runtime_dir.join(display).exists()
+    {
+        return None;
     }
+
+    let mut sockets: Vec<OsString> = fs::read_dir(&runtime_dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("wayland-"))
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_ok_and(|file_type| file_type.is_socket())
+        })
+        .map(|entry| entry.file_name())
+        .collect();
+    sockets.sort();
+
+    sockets.into_iter().next()
+}
+
+/// Name of a running gamma control client other than this one
+fn find_gamma_client() -> Option<String> {
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+        let comm = comm.trim_end();
+
+        // The kernel truncates process names to 15 bytes
+        GAMMA_CLIENTS
+            .iter()
+            .any(|client| client.get(..15).unwrap_or(client) == comm)
+            .then(|| format!("{comm} (pid {})", entry.file_name().to_string_lossy()))
+    })
 }
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
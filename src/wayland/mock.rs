@@ -0,0 +1,631 @@
+//! Compositor speaking just enough of the Wayland wire protocol to test gamma control, advertising
+//! `wl_output` and `zwlr_gamma_control_manager_v1`
+
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    fs::{self, File},
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Set in the child process running a test against the mock compositor
+const CHILD_MARKER: &str = "AUTOMATIC_REDSHIFT_MOCK_COMPOSITOR";
+const SOCKET_NAME: &str = "wayland-mock";
+
+const DISPLAY_ID: u32 = 1;
+const GAMMA_MANAGER_GLOBAL: u32 = 1;
+const OUTPUT_VERSION: u32 = 4;
+
+/// An output the compositor advertises
+#[derive(Debug, Clone)]
+pub struct MockOutput {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub description: String,
+    pub ramp_size: u32,
+    /// Gamma control requests answered with `failed`, as if another client held the output
+    pub refusals: u32,
+}
+
+impl MockOutput {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            make: "Mock".to_owned(),
+            model: "Display".to_owned(),
+            description: format!("Mock Display ({name})"),
+            ramp_size: 256,
+            refusals: 0,
+        }
+    }
+
+    pub fn ramp_size(self, ramp_size: u32) -> Self {
+        Self { ramp_size, ..self }
+    }
+
+    pub fn refusals(self, refusals: u32) -> Self {
+        Self { refusals, ..self }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Advertised outputs by global name
+    outputs: Vec<(u32, MockOutput)>,
+    next_global: u32,
+    /// Ramps the client set, by output name
+    ramps: Vec<(String, Vec<u16>)>,
+    /// Gamma control requests by output name
+    requests: Vec<String>,
+    /// Outputs the client holds the gamma control of
+    controlled: Vec<String>,
+    connections: usize,
+    commands: Vec<Command>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Add(u32),
+    Remove(u32),
+    Disconnect,
+}
+
+/// Runs on its own thread until dropped
+pub struct MockCompositor {
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    socket: PathBuf,
+}
+
+/// Run `test` of this module path in a child process whose environment points at a socket for
+/// the mock compositor, returning whether this is the child
+///
+/// wayrs only finds the compositor through the environment, which can't be changed while other
+/// tests of the binary may read it, so it is set before the child starts instead.
+pub fn in_child_process(test: &str) -> bool {
+    if env::var_os(CHILD_MARKER).is_some() {
+        return true;
+    }
+
+    let module = module_path!()
+        .split_once("::")
+        .map_or(module_path!(), |(_, path)| path)
+        .trim_end_matches("::mock");
+    let name = format!("{module}::tests::{test}");
+    let dir = env::temp_dir().join(format!(
+        "automatic-redshift-test-{}-{test}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let output = process::Command::new(env::current_exe().unwrap())
+        .args(["--exact", &name, "--nocapture", "--test-threads=1"])
+        .env(CHILD_MARKER, "1")
+        .env("XDG_RUNTIME_DIR", &dir)
+        .env("WAYLAND_DISPLAY", SOCKET_NAME)
+        .env_remove("WAYLAND_SOCKET")
+        .output()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains("1 passed"),
+        "{name} failed in the child process:\n{stdout}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    false
+}
+
+impl MockCompositor {
+    /// Listen on the socket the environment points at, see [`in_child_process`]
+    pub fn start(outputs: Vec<MockOutput>) -> Self {
+        Self::start_at(SOCKET_NAME, outputs)
+    }
+
+    /// Listen on another socket in the runtime directory, as a compositor of a new session would
+    pub fn start_at(socket_name: &str, outputs: Vec<MockOutput>) -> Self {
+        assert!(
+            env::var_os(CHILD_MARKER).is_some(),
+            "the mock compositor only runs in a child process"
+        );
+        let socket = PathBuf::from(env::var_os("XDG_RUNTIME_DIR").unwrap()).join(socket_name);
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        listener.set_nonblocking(true).unwrap();
+
+        let state = Arc::new(Mutex::new(State {
+            next_global: GAMMA_MANAGER_GLOBAL + 1,
+            ..State::default()
+        }));
+        for output in outputs {
+            let mut state = state.lock().unwrap();
+            let global = state.next_global;
+            state.next_global += 1;
+            state.outputs.push((global, output));
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let state = state.clone();
+            let stop = stop.clone();
+            move || serve(&listener, &state, &stop)
+        });
+
+        Self {
+            state,
+            stop,
+            thread: Some(thread),
+            socket,
+        }
+    }
+
+    /// Plug in an output
+    pub fn add_output(&self, output: MockOutput) {
+        let mut state = self.state.lock().unwrap();
+        let global = state.next_global;
+        state.next_global += 1;
+        state.outputs.push((global, output));
+        state.commands.push(Command::Add(global));
+    }
+
+    /// Unplug an output
+    pub fn remove_output(&self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        let global = state
+            .outputs
+            .iter()
+            .find(|(_, output)| output.name == name)
+            .map(|&(global, _)| global)
+            .expect("no such output");
+        state.outputs.retain(|&(other, _)| other != global);
+        state.controlled.retain(|other| other != name);
+        state.commands.push(Command::Remove(global));
+    }
+
+    /// Drop the connection of the client, as if the compositor crashed and was started again
+    pub fn disconnect(&self) {
+        self.state
+            .lock()
+            .unwrap()
+            .commands
+            .push(Command::Disconnect);
+    }
+
+    /// Ramps set on the output so far
+    pub fn ramps(&self, output: &str) -> Vec<Vec<u16>> {
+        let state = self.state.lock().unwrap();
+
+        state
+            .ramps
+            .iter()
+            .filter(|(name, _)| name == output)
+            .map(|(_, ramp)| ramp.clone())
+            .collect()
+    }
+
+    /// Number of times the client asked for the gamma control of the output
+    pub fn gamma_control_requests(&self, output: &str) -> usize {
+        let state = self.state.lock().unwrap();
+
+        state.requests.iter().filter(|name| *name == output).count()
+    }
+
+    pub fn has_gamma_control(&self, output: &str) -> bool {
+        let state = self.state.lock().unwrap();
+
+        state.controlled.iter().any(|name| name == output)
+    }
+
+    /// Number of clients that connected so far
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+}
+
+impl Drop for MockCompositor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_file(&self.socket);
+    }
+}
+
+fn serve(listener: &UnixListener, state: &Mutex<State>, stop: &AtomicBool) {
+    let mut client: Option<Client> = None;
+
+    while !stop.load(Ordering::SeqCst) {
+        if let Ok((stream, _)) = listener.accept() {
+            stream.set_nonblocking(true).unwrap();
+            let mut state = state.lock().unwrap();
+            state.connections += 1;
+            state.controlled.clear();
+            client = Some(Client::new(stream));
+        }
+
+        let commands = mem::take(&mut state.lock().unwrap().commands);
+        for command in commands {
+            match command {
+                Command::Disconnect => {
+                    client = None;
+                    state.lock().unwrap().controlled.clear();
+                }
+                command => {
+                    if let Some(client) = &mut client {
+                        client.announce(&command);
+                    }
+                }
+            }
+        }
+
+        if let Some(connected) = &mut client {
+            match connected.receive() {
+                Ok(true) => connected.handle_requests(&mut state.lock().unwrap()),
+                Ok(false) => {
+                    client = None;
+                    state.lock().unwrap().controlled.clear();
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                Err(err) => panic!("Failed to receive requests: {err}"),
+            }
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Object {
+    Display,
+    Registry,
+    Callback,
+    /// Global name of the output
+    Output(u32),
+    GammaManager,
+    /// Global name of the output
+    GammaControl(u32),
+}
+
+enum Arg<'a> {
+    Uint(u32),
+    Int(i32),
+    Str(&'a str),
+}
+
+struct Client {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    fds: VecDeque<OwnedFd>,
+    objects: HashMap<u32, Object>,
+}
+
+impl Client {
+    fn new(stream: UnixStream) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            fds: VecDeque::new(),
+            objects: HashMap::from([(DISPLAY_ID, Object::Display)]),
+        }
+    }
+
+    /// Read the available requests along with the file descriptors, `false` once the client
+    /// disconnected
+    fn receive(&mut self) -> io::Result<bool> {
+        let mut data = [0u8; 4096];
+        let mut control = [0u64; 32];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let mut message: libc::msghdr = unsafe { mem::zeroed() };
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = mem::size_of_val(&control) as _;
+
+        let read = unsafe {
+            libc::recvmsg(
+                self.stream.as_raw_fd(),
+                &mut message,
+                libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC,
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if read == 0 {
+            return Ok(false);
+        }
+        self.buffer.extend_from_slice(&data[..read as usize]);
+
+        unsafe {
+            let mut header = libc::CMSG_FIRSTHDR(&message);
+            while !header.is_null() {
+                if (*header).cmsg_level == libc::SOL_SOCKET
+                    && (*header).cmsg_type == libc::SCM_RIGHTS
+                {
+                    let data = libc::CMSG_DATA(header).cast::<i32>();
+                    let count = ((*header).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / 4;
+                    for index in 0..count {
+                        let fd = data.add(index).read_unaligned();
+                        self.fds.push_back(OwnedFd::from_raw_fd(fd));
+                    }
+                }
+                header = libc::CMSG_NXTHDR(&message, header);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn handle_requests(&mut self, state: &mut State) {
+        while self.buffer.len() >= 8 {
+            let object = u32::from_ne_bytes(self.buffer[0..4].try_into().unwrap());
+            let word = u32::from_ne_bytes(self.buffer[4..8].try_into().unwrap());
+            let size = (word >> 16) as usize;
+            let opcode = word & 0xffff;
+            if self.buffer.len() < size {
+                return;
+            }
+
+            let args: Vec<u8> = self.buffer[8..size].to_vec();
+            self.buffer.drain(..size);
+            self.handle(state, object, opcode, &mut Args(&args));
+        }
+    }
+
+    fn handle(&mut self, state: &mut State, id: u32, opcode: u32, args: &mut Args) {
+        let object = *self.objects.get(&id).expect("request for unknown object");
+
+        match (object, opcode) {
+            // wl_display.sync
+            (Object::Display, 0) => {
+                let callback = args.uint();
+                self.objects.insert(callback, Object::Callback);
+                self.send(callback, 0, &[Arg::Uint(0)]);
+                self.destroyed(callback);
+            }
+            // wl_display.get_registry
+            (Object::Display, 1) => {
+                let registry = args.uint();
+                self.objects.insert(registry, Object::Registry);
+                self.global(
+                    registry,
+                    GAMMA_MANAGER_GLOBAL,
+                    "zwlr_gamma_control_manager_v1",
+                    1,
+                );
+                for (global, _) in &state.outputs {
+                    self.global(registry, *global, "wl_output", OUTPUT_VERSION);
+                }
+            }
+            // wl_registry.bind
+            (Object::Registry, 0) => {
+                let global = args.uint();
+                let _interface = args.string();
+                let _version = args.uint();
+                let id = args.uint();
+
+                if global == GAMMA_MANAGER_GLOBAL {
+                    self.objects.insert(id, Object::GammaManager);
+                } else {
+                    self.objects.insert(id, Object::Output(global));
+                    let (_, output) = state
+                        .outputs
+                        .iter()
+                        .find(|(other, _)| *other == global)
+                        .expect("bound an unknown output");
+                    self.describe(id, output);
+                }
+            }
+            // wl_output.release, zwlr_gamma_control_manager_v1.destroy
+            (Object::Output(_), 0) | (Object::GammaManager, 1) => self.destroyed(id),
+            // zwlr_gamma_control_manager_v1.get_gamma_control
+            (Object::GammaManager, 0) => {
+                let gamma_control = args.uint();
+                let Some(&Object::Output(global)) = self.objects.get(&args.uint()) else {
+                    panic!("gamma control requested for an unknown output");
+                };
+                self.objects
+                    .insert(gamma_control, Object::GammaControl(global));
+
+                let Some((_, output)) =
+                    state.outputs.iter_mut().find(|(other, _)| *other == global)
+                else {
+                    // Removed in the meantime
+                    self.send(gamma_control, 1, &[]);
+                    return;
+                };
+                state.requests.push(output.name.clone());
+
+                if output.refusals > 0 {
+                    output.refusals -= 1;
+                    self.send(gamma_control, 1, &[]);
+                } else {
+                    state.controlled.push(output.name.clone());
+                    self.send(gamma_control, 0, &[Arg::Uint(output.ramp_size)]);
+                }
+            }
+            // zwlr_gamma_control_v1.set_gamma
+            (Object::GammaControl(global), 0) => {
+                let fd = self
+                    .fds
+                    .pop_front()
+                    .expect("set_gamma without a file descriptor");
+                let mut bytes = Vec::new();
+                File::from(fd).read_to_end(&mut bytes).unwrap();
+                let ramp = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+                    .collect();
+
+                let name = output_name(state, global);
+                state.ramps.push((name, ramp));
+            }
+            // zwlr_gamma_control_v1.destroy
+            (Object::GammaControl(global), 1) => {
+                let name = output_name(state, global);
+                if let Some(index) = state.controlled.iter().position(|other| *other == name) {
+                    state.controlled.remove(index);
+                }
+                self.destroyed(id);
+            }
+            (object, opcode) => panic!("unexpected request {opcode} on {object:?}"),
+        }
+    }
+
+    fn announce(&mut self, command: &Command) {
+        let registries: Vec<u32> = self
+            .objects
+            .iter()
+            .filter(|(_, object)| **object == Object::Registry)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for registry in registries {
+            match *command {
+                Command::Add(global) => self.global(registry, global, "wl_output", OUTPUT_VERSION),
+                Command::Remove(global) => self.send(registry, 1, &[Arg::Uint(global)]),
+                Command::Disconnect => (),
+            }
+        }
+
+        // Outputs go away with their gamma control
+        if let Command::Remove(global) = *command {
+            let gamma_controls: Vec<u32> = self
+                .objects
+                .iter()
+                .filter(|(_, object)| **object == Object::GammaControl(global))
+                .map(|(&id, _)| id)
+                .collect();
+            for gamma_control in gamma_controls {
+                self.send(gamma_control, 1, &[]);
+            }
+        }
+    }
+
+    fn global(&mut self, registry: u32, global: u32, interface: &str, version: u32) {
+        self.send(
+            registry,
+            0,
+            &[Arg::Uint(global), Arg::Str(interface), Arg::Uint(version)],
+        );
+    }
+
+    /// Send the metadata of the output, like a compositor does right after binding
+    fn describe(&mut self, id: u32, output: &MockOutput) {
+        // geometry
+        self.send(
+            id,
+            0,
+            &[
+                Arg::Int(0),
+                Arg::Int(0),
+                Arg::Int(600),
+                Arg::Int(340),
+                Arg::Int(0),
+                Arg::Str(&output.make),
+                Arg::Str(&output.model),
+                Arg::Int(0),
+            ],
+        );
+        // mode, current and preferred
+        self.send(
+            id,
+            1,
+            &[
+                Arg::Uint(3),
+                Arg::Int(1920),
+                Arg::Int(1080),
+                Arg::Int(60000),
+            ],
+        );
+        // name, description and done
+        self.send(id, 4, &[Arg::Str(&output.name)]);
+        self.send(id, 5, &[Arg::Str(&output.description)]);
+        self.send(id, 2, &[]);
+    }
+
+    /// Confirm the object is gone so the client can reuse its ID
+    fn destroyed(&mut self, id: u32) {
+        self.objects.remove(&id);
+        self.send(DISPLAY_ID, 1, &[Arg::Uint(id)]);
+    }
+
+    fn send(&mut self, object: u32, opcode: u32, args: &[Arg]) {
+        let mut payload = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => payload.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Int(value) => payload.extend_from_slice(&value.to_ne_bytes()),
+                Arg::Str(value) => {
+                    let length = value.len() + 1;
+                    payload.extend_from_slice(&(length as u32).to_ne_bytes());
+                    payload.extend_from_slice(value.as_bytes());
+                    payload.resize(payload.len() + 4 - value.len() % 4, 0);
+                }
+            }
+        }
+
+        let size = (8 + payload.len()) as u32;
+        let mut message = Vec::with_capacity(size as usize);
+        message.extend_from_slice(&object.to_ne_bytes());
+        message.extend_from_slice(&(size << 16 | opcode).to_ne_bytes());
+        message.extend_from_slice(&payload);
+
+        // The client may be gone already, which the next receive notices
+        let _ = (&self.stream).write_all(&message);
+    }
+}
+
+fn output_name(state: &State, global: u32) -> String {
+    state
+        .outputs
+        .iter()
+        .find(|(other, _)| *other == global)
+        .map_or_else(
+            || format!("removed output {global}"),
+            |(_, output)| output.name.clone(),
+        )
+}
+
+/// Arguments of a request
+struct Args<'a>(&'a [u8]);
+
+impl Args<'_> {
+    fn uint(&mut self) -> u32 {
+        let (value, rest) = self.0.split_at(4);
+        self.0 = rest;
+
+        u32::from_ne_bytes(value.try_into().unwrap())
+    }
+
+    fn string(&mut self) -> String {
+        let length = self.uint() as usize;
+        let padded = length.div_ceil(4) * 4;
+        let (value, rest) = self.0.split_at(padded);
+        self.0 = rest;
+
+        String::from_utf8_lossy(&value[..length.saturating_sub(1)]).into_owned()
+    }
+}
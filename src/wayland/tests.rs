@@ -0,0 +1,275 @@
+//! End to end tests against the mock compositor
+
+use super::{
+    mock::{MockCompositor, MockOutput, in_child_process},
+    *,
+};
+use crate::color::fill_colorramp;
+use tokio::time::sleep;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Ramp the compositor should receive for `color`, red, green and blue after another
+fn expected_ramp(size: usize, color: ColorSetting) -> Vec<u16> {
+    let mut table = vec![0u16; size * 3];
+    let (r, rest) = table.split_at_mut(size);
+    let (g, b) = rest.split_at_mut(size);
+    fill_colorramp(r, g, b, size, color).unwrap();
+
+    table
+}
+
+/// Handle events until `done` holds, returning the output errors reported in the meantime
+async fn poll_until(wayland: &mut Wayland, mut done: impl FnMut() -> bool) -> Vec<OutputError> {
+    let deadline = Instant::now() + TIMEOUT;
+    let mut errors = Vec::new();
+
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out, errors: {errors:?}");
+        select! {
+            new_errors = wayland.poll() => errors.extend(new_errors),
+            _ = sleep(Duration::from_millis(5)) => (),
+        }
+    }
+
+    errors
+}
+
+/// Handle events for a while, for asserting that nothing happens
+async fn poll_for(wayland: &mut Wayland, duration: Duration) -> Vec<OutputError> {
+    let deadline = Instant::now() + duration;
+
+    poll_until(wayland, || Instant::now() >= deadline).await
+}
+
+#[tokio::test]
+async fn applies_ramps_to_all_outputs() {
+    if !in_child_process("applies_ramps_to_all_outputs") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![
+        MockOutput::new("DP-1"),
+        MockOutput::new("DP-2").ramp_size(1024),
+    ]);
+    let mut wayland = Wayland::new(Vec::new());
+
+    let errors = poll_until(&mut wayland, || {
+        mock.ramps("DP-1").len() == 1 && mock.ramps("DP-2").len() == 1
+    })
+    .await;
+    assert!(errors.is_empty(), "{errors:?}");
+    let initial = ColorSetting::new(6500);
+    assert_eq!(mock.ramps("DP-1")[0], expected_ramp(256, initial));
+    assert_eq!(mock.ramps("DP-2")[0], expected_ramp(1024, initial));
+
+    let color = ColorSetting {
+        temperature: 3400,
+        brightness: 0.8,
+    };
+    wayland.set_temperature(color);
+    let errors = poll_until(&mut wayland, || {
+        mock.ramps("DP-1").len() == 2 && mock.ramps("DP-2").len() == 2
+    })
+    .await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.ramps("DP-1")[1], expected_ramp(256, color));
+    assert_eq!(mock.ramps("DP-2")[1], expected_ramp(1024, color));
+
+    // Outputs already showing the color are left alone
+    wayland.set_temperature(color);
+    poll_for(&mut wayland, Duration::from_millis(100)).await;
+    assert_eq!(mock.ramps("DP-1").len(), 2);
+    assert_eq!(mock.ramps("DP-2").len(), 2);
+}
+
+#[tokio::test]
+async fn follows_hotplugged_outputs() {
+    if !in_child_process("follows_hotplugged_outputs") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![MockOutput::new("eDP-1")]);
+    let mut wayland = Wayland::new(Vec::new());
+    poll_until(&mut wayland, || mock.ramps("eDP-1").len() == 1).await;
+
+    let color = ColorSetting::new(4000);
+    wayland.set_temperature(color);
+    poll_until(&mut wayland, || mock.ramps("eDP-1").len() == 2).await;
+
+    // A new output gets the current color right away
+    mock.add_output(MockOutput::new("HDMI-A-1").ramp_size(512));
+    let errors = poll_until(&mut wayland, || mock.ramps("HDMI-A-1").len() == 1).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.ramps("HDMI-A-1")[0], expected_ramp(512, color));
+    assert!(mock.has_gamma_control("HDMI-A-1"));
+
+    // A removed one is forgotten without affecting the others
+    mock.remove_output("HDMI-A-1");
+    poll_for(&mut wayland, Duration::from_millis(100)).await;
+    let color = ColorSetting::new(3000);
+    wayland.set_temperature(color);
+    let errors = poll_until(&mut wayland, || mock.ramps("eDP-1").len() == 3).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.ramps("eDP-1")[2], expected_ramp(256, color));
+    assert_eq!(mock.ramps("HDMI-A-1").len(), 1);
+    assert!(wayland.conflicts().is_empty());
+}
+
+#[tokio::test]
+async fn retries_gamma_control_held_by_another_client() {
+    if !in_child_process("retries_gamma_control_held_by_another_client") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![
+        MockOutput::new("DP-1"),
+        MockOutput::new("DP-2").refusals(1),
+    ]);
+    let mut wayland = Wayland::new(Vec::new());
+
+    poll_until(&mut wayland, || {
+        mock.ramps("DP-1").len() == 1 && mock.gamma_control_requests("DP-2") == 1
+    })
+    .await;
+    poll_for(&mut wayland, Duration::from_millis(100)).await;
+    assert_eq!(wayland.conflicts().len(), 1);
+    assert!(wayland.conflicts()[0].starts_with("DP-2"));
+    assert!(mock.ramps("DP-2").is_empty());
+
+    // Taken over once the other client is gone
+    let errors = poll_until(&mut wayland, || mock.ramps("DP-2").len() == 1).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.gamma_control_requests("DP-2"), 2);
+    assert_eq!(
+        mock.ramps("DP-2")[0],
+        expected_ramp(256, ColorSetting::new(6500))
+    );
+    assert!(wayland.conflicts().is_empty());
+}
+
+#[tokio::test]
+async fn applies_output_settings() {
+    if !in_child_process("applies_output_settings") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![MockOutput::new("DP-1"), MockOutput::new("HDMI-A-1")]);
+    let configs = vec![
+        OutputConfig {
+            name: Some("HDMI-A-1".to_owned()),
+            enabled: false,
+            ..OutputConfig::default()
+        },
+        OutputConfig {
+            description: Some("(DP-1)".to_owned()),
+            brightness: 0.5,
+            ..OutputConfig::default()
+        },
+    ];
+    let mut wayland = Wayland::new(configs);
+
+    let color = ColorSetting {
+        temperature: 4500,
+        brightness: 0.8,
+    };
+    wayland.set_temperature(color);
+    poll_until(&mut wayland, || !mock.ramps("DP-1").is_empty()).await;
+    poll_for(&mut wayland, Duration::from_millis(100)).await;
+
+    let dimmed = ColorSetting {
+        brightness: 0.4,
+        ..color
+    };
+    assert_eq!(mock.ramps("DP-1").last(), Some(&expected_ramp(256, dimmed)));
+    assert_eq!(mock.gamma_control_requests("HDMI-A-1"), 0);
+    assert!(mock.ramps("HDMI-A-1").is_empty());
+}
+
+#[tokio::test]
+async fn reports_and_disables_failing_outputs() {
+    if !in_child_process("reports_and_disables_failing_outputs") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![MockOutput::new("DP-1")]);
+    let mut wayland = Wayland::new(Vec::new());
+    poll_until(&mut wayland, || mock.ramps("DP-1").len() == 1).await;
+
+    // Below the supported range, so computing the ramp fails
+    let color = ColorSetting::new(500);
+    for _ in 0..MAX_OUTPUT_FAILURES {
+        wayland.set_temperature(color);
+        let errors = poll_for(&mut wayland, Duration::from_millis(50)).await;
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].output, "DP-1");
+    }
+
+    // Left alone from now on, without further errors
+    wayland.set_temperature(color);
+    let errors = poll_for(&mut wayland, Duration::from_millis(50)).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    wayland.set_temperature(ColorSetting::new(4000));
+    poll_for(&mut wayland, Duration::from_millis(50)).await;
+    assert_eq!(mock.ramps("DP-1").len(), 1);
+}
+
+#[tokio::test]
+async fn reapplies_after_reconnecting() {
+    if !in_child_process("reapplies_after_reconnecting") {
+        return;
+    }
+
+    let mock = MockCompositor::start(vec![MockOutput::new("DP-1")]);
+    let mut wayland = Wayland::new(Vec::new());
+    poll_until(&mut wayland, || mock.ramps("DP-1").len() == 1).await;
+
+    mock.disconnect();
+    let deadline = Instant::now() + TIMEOUT;
+    while wayland.session.is_some() {
+        assert!(Instant::now() < deadline, "connection loss not noticed");
+        wayland.poll().await;
+    }
+
+    // The color set while disconnected is applied once the compositor is back
+    let color = ColorSetting::new(3600);
+    wayland.set_temperature(color);
+    let errors = poll_until(&mut wayland, || mock.ramps("DP-1").len() == 2).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.connections(), 2);
+    assert_eq!(mock.ramps("DP-1")[1], expected_ramp(256, color));
+}
+
+#[tokio::test]
+async fn connects_to_a_compositor_started_later_and_after_restarts() {
+    if !in_child_process("connects_to_a_compositor_started_later_and_after_restarts") {
+        return;
+    }
+
+    // Nothing to connect to yet, which isn't an error
+    let mut wayland = Wayland::new(Vec::new());
+    let color = ColorSetting::new(3600);
+    wayland.set_temperature(color);
+    let errors = poll_for(&mut wayland, Duration::from_millis(100)).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert!(wayland.session.is_none());
+
+    let mock = MockCompositor::start(vec![MockOutput::new("DP-1")]);
+    let errors = poll_until(&mut wayland, || mock.ramps("DP-1").len() == 1).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.ramps("DP-1")[0], expected_ramp(256, color));
+
+    // The compositor goes away along with its socket and comes back on another one
+    drop(mock);
+    let deadline = Instant::now() + TIMEOUT;
+    while wayland.session.is_some() {
+        assert!(Instant::now() < deadline, "connection loss not noticed");
+        wayland.poll().await;
+    }
+    let mock = MockCompositor::start_at("wayland-1", vec![MockOutput::new("DP-1")]);
+
+    let errors = poll_until(&mut wayland, || mock.ramps("DP-1").len() == 1).await;
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(mock.ramps("DP-1")[0], expected_ramp(256, color));
+    assert_eq!(env::var_os("WAYLAND_DISPLAY").unwrap(), "wayland-1");
+}